@@ -0,0 +1,389 @@
+use crate::func::{
+    fn_add, fn_const, fn_cos, fn_div, fn_exp, fn_log, fn_mul, fn_neg, fn_pdv, fn_poly, fn_powi,
+    fn_prod, fn_sin, fn_sub, fn_sum, fn_var, Function,
+};
+use crate::polynomial::Polynomial;
+use crate::rational::{PartialFraction, Rational};
+
+// =============================================================================
+
+/// most integration by parts steps `fn_integrate` takes, i.e. the highest
+/// degree of a polynomial factor it integrates against exp, sin or cos
+const MAX_PARTS_STEPS: usize = 32;
+
+/// symbolically computes an antiderivative of f with respect to the var-th
+/// variable, or None if it can't find one
+///
+/// Knows about:
+/// - linearity: add, sub, neg, sum and factors constant in the variable
+/// - polynomials: `Poly`, `PolyF` with coefficients constant in the variable
+/// - table lookup: sin, cos, tan, exp, log and powers of a linear argument
+/// - u-substitution: c * u'(x) * g(u(x)) for g from the table, or g(u) = u
+/// - integration by parts: polynomial * exp/sin/cos of a linear argument
+/// - rational functions of x_0 by partial fractions, as long as no arctan is
+///   needed (a term over an irreducible quadratic q must be a multiple of q'/q^k)
+///
+/// the constant of integration is 0. log|u| is written as log(u^2)/2, so the
+/// result holds on either side of a singularity, but not across it
+pub fn fn_integrate(f: &Function, var: usize) -> Option<Function> {
+    if !f.depends_on(var) {
+        return Some(fn_mul(f.clone(), fn_var(var)));
+    }
+
+    integrate_rules(f, var).or_else(|| integrate_rational(f, var))
+}
+
+fn integrate_rules(f: &Function, var: usize) -> Option<Function> {
+    match f {
+        Function::Var(_) => Some(fn_mul(fn_const(0.5), fn_powi(fn_var(var), 2))),
+        Function::Add(f1, f2) => Some(fn_add(fn_integrate(f1, var)?, fn_integrate(f2, var)?)),
+        Function::Sub(f1, f2) => Some(fn_sub(fn_integrate(f1, var)?, fn_integrate(f2, var)?)),
+        Function::Neg(g) => Some(fn_neg(fn_integrate(g, var)?)),
+        Function::Sum(fs) => integrate_each(fs, var).map(fn_sum),
+        // depends on the variable, so var == 0
+        Function::Poly(coeffs) => Some(
+            Polynomial::new(coeffs.clone())
+                .antiderivative()
+                .to_function(),
+        ),
+        // x_i is a constant factor of every term
+        Function::PolyF(fs, i) if *i != var => Some(Function::PolyF(integrate_each(fs, var)?, *i)),
+        Function::PolyF(fs, i) if fs.iter().all(|g| !g.depends_on(var)) => {
+            let shifted = fs
+                .iter()
+                .enumerate()
+                .map(|(k, g)| fn_div(g.clone(), fn_const((k + 1) as f64)));
+            Some(Function::PolyF(
+                std::iter::once(fn_const(0.0)).chain(shifted).collect(),
+                *i,
+            ))
+        }
+        Function::PolyF(fs, i) => {
+            let terms = fs
+                .iter()
+                .enumerate()
+                .map(|(k, g)| fn_mul(g.clone(), fn_powi(fn_var(*i), k as i32)))
+                .collect();
+            fn_integrate(&fn_sum(terms), var)
+        }
+        Function::Mul(_, _) | Function::Prod(_) | Function::Div(_, _) => {
+            integrate_product(factors(f), var)
+        }
+        _ => integrate_substitution(f, None, var),
+    }
+}
+
+/// integrates a rational function of x_0 term by term of its partial fractions
+fn integrate_rational(f: &Function, var: usize) -> Option<Function> {
+    if var != 0 {
+        return None;
+    }
+    let pf = Rational::from_function(f)?.partial_fractions()?;
+
+    let polynomial = Polynomial::new(pf.polynomial)
+        .antiderivative()
+        .to_function();
+    let terms = pf
+        .terms
+        .iter()
+        .map(integrate_partial_fraction)
+        .collect::<Option<Vec<_>>>()?;
+    Some(fn_sum(std::iter::once(polynomial).chain(terms).collect()))
+}
+
+/// int a/(x - r)^k dx is a log|x - r| for k = 1 and a/(1 - k) (x - r)^(1 - k)
+/// otherwise. with u = x^2 + px + q the numerator is b/2 u' + (c - bp/2), so
+/// int (bx + c)/u^k dx works the same way if the second part vanishes
+fn integrate_partial_fraction(term: &PartialFraction) -> Option<Function> {
+    let (a, u, power) = match *term {
+        PartialFraction::Linear { a, root, power } => (a, fn_poly(vec![-root, 1.0]), power),
+        PartialFraction::Quadratic { b, c, p, q, power } => {
+            let rest = c - b * p / 2.0;
+            if rest.abs() > ARCTAN_TOLERANCE * (1.0 + c.abs()) {
+                return None;
+            }
+            (b / 2.0, fn_poly(vec![q, p, 1.0]), power)
+        }
+    };
+    let k = power as i32;
+    Some(match k {
+        1 => fn_mul(fn_const(a), log_abs(u)),
+        _ => fn_mul(fn_const(a / (1 - k) as f64), fn_powi(u, 1 - k)),
+    })
+}
+
+/// the part of a quadratic term that would need an arctan is dropped if it is
+/// below this, relative to the numerator
+const ARCTAN_TOLERANCE: f64 = 1e-9;
+
+fn integrate_each(fs: &[Function], var: usize) -> Option<Vec<Function>> {
+    fs.iter().map(|g| fn_integrate(g, var)).collect()
+}
+
+/// splits f into factors f = f_1 * ... * f_n, divisions become negative powers
+fn factors(f: &Function) -> Vec<Function> {
+    match f {
+        Function::Mul(f1, f2) => [factors(f1), factors(f2)].concat(),
+        Function::Prod(fs) => fs.iter().flat_map(factors).collect(),
+        Function::Div(f1, f2) => {
+            let mut fs = factors(f1);
+            fs.push(match f2.as_ref() {
+                Function::PowI(g, n) => fn_powi(*g.clone(), -n),
+                g => fn_powi(g.clone(), -1),
+            });
+            fs
+        }
+        f => vec![f.clone()],
+    }
+}
+
+/// integrates the product of the factors: those constant in the variable are
+/// pulled out, the rest integrated by u-substitution or by parts
+fn integrate_product(factors: Vec<Function>, var: usize) -> Option<Function> {
+    let (constant, varying): (Vec<_>, Vec<_>) =
+        factors.into_iter().partition(|g| !g.depends_on(var));
+
+    let integral = match varying.as_slice() {
+        [] => fn_var(var),
+        [g] => fn_integrate(g, var)?,
+        _ => {
+            // one factor as g(u(x)), the others as c * u'(x)
+            let others = |i: usize| {
+                let mut rest = varying.clone();
+                rest.remove(i);
+                match rest.len() {
+                    1 => rest.pop().unwrap(),
+                    _ => fn_prod(rest),
+                }
+            };
+            (0..varying.len())
+                .find_map(|i| integrate_substitution(&varying[i], Some(&others(i)), var))
+                .or_else(|| {
+                    (0..varying.len())
+                        .find_map(|i| integrate_by_parts(&others(i), &varying[i], var))
+                })?
+        }
+    };
+
+    Some(
+        constant
+            .into_iter()
+            .rfold(integral, |acc, c| fn_mul(c, acc)),
+    )
+}
+
+/// integrates w(x) * g(x) (w = 1 if None) by writing g(x) = G(u(x)) for a G
+/// with known antiderivative H, which works if w = c * u' for a constant c:
+/// int w(x) * G(u(x)) dx = c * H(u(x))
+fn integrate_substitution(g: &Function, w: Option<&Function>, var: usize) -> Option<Function> {
+    substitutions(g)
+        .into_iter()
+        .find_map(|(u, antiderivative)| {
+            let du = fn_pdv(&u, var);
+            let c = match w {
+                Some(w) => fn_div(w.clone(), du),
+                None => fn_div(fn_const(1.0), du),
+            };
+            let c = constant_factor(&c, var)?;
+            Some(fn_mul(c, antiderivative))
+        })
+}
+
+/// returns f if it is a finite constant in x_var, as an expression in the other
+/// variables or, failing that, as a number
+///
+/// the simplifier can't cancel everything (e.g. a `Poly` against a product),
+/// so a number is found by evaluating f, and accepted if f takes the same
+/// value at every one of the fixed `CONSTANT_PROBES`. the result only depends
+/// on f, never on chance
+fn constant_factor(f: &Function, var: usize) -> Option<Function> {
+    let c = f.simplify();
+    if !c.depends_on(var) {
+        return match c {
+            Function::Const(c) if !c.is_finite() => None,
+            c => Some(c),
+        };
+    }
+
+    let n = f.num_vars();
+    let probe = |k: usize| -> f64 {
+        let args: Vec<f64> = (0..n)
+            .map(|j| CONSTANT_PROBES[(k + j) % CONSTANT_PROBES.len()])
+            .collect();
+        f.eval(args)
+    };
+    let value = probe(0);
+    let constant = (0..CONSTANT_PROBES.len()).all(|k| {
+        let y = probe(k);
+        y.is_finite() && (y - value).abs() <= 1e-9 * (1.0 + value.abs())
+    });
+    constant.then(|| fn_const(value))
+}
+
+/// points where `constant_factor` evaluates, arbitrary enough to avoid the
+/// special points of common functions
+const CONSTANT_PROBES: [f64; 8] = [
+    0.577_215_664_9,
+    -1.374_520_193_2,
+    1.839_286_755_2,
+    -0.261_497_212_8,
+    1.324_717_957_2,
+    -0.915_965_594_2,
+    0.412_454_033_6,
+    -1.202_056_903_2,
+];
+
+/// the ways of writing g = G(u) with a known antiderivative H of G, as the
+/// pairs (u, H(u))
+fn substitutions(g: &Function) -> Vec<(Function, Function)> {
+    let table = match g {
+        Function::Sin(u) => Some((u, fn_neg(fn_cos(*u.clone())))),
+        Function::Cos(u) => Some((u, fn_sin(*u.clone()))),
+        // -log|cos(u)|
+        Function::Tan(u) => Some((u, fn_neg(log_abs(fn_cos(*u.clone()))))),
+        Function::Exp(u) => Some((u, fn_exp(*u.clone()))),
+        Function::Log(u) => Some((
+            u,
+            fn_sub(fn_mul(*u.clone(), fn_log(*u.clone())), *u.clone()),
+        )),
+        Function::PowI(u, -1) => Some((u, log_abs(*u.clone()))),
+        Function::PowI(u, n) => Some((
+            u,
+            fn_div(fn_powi(*u.clone(), n + 1), fn_const((n + 1) as f64)),
+        )),
+        _ => None,
+    };
+
+    // G(u) = u, so int u * u' dx = u^2 / 2
+    let identity = (g.clone(), fn_mul(fn_const(0.5), fn_powi(g.clone(), 2)));
+
+    table
+        .map(|(u, antiderivative)| (*u.clone(), antiderivative))
+        .into_iter()
+        .chain(std::iter::once(identity))
+        .collect()
+}
+
+/// integrates p(x) * h(x) for a polynomial p and h = exp, sin or cos of a
+/// linear argument, by parts until the derivative of p vanishes:
+/// int p * h = p * H_1 - p' * H_2 + p'' * H_3 - ..., H_k the k-th antiderivative of h
+fn integrate_by_parts(p: &Function, h: &Function, var: usize) -> Option<Function> {
+    if !matches!(h, Function::Exp(_) | Function::Sin(_) | Function::Cos(_))
+        || !is_polynomial(p, var)
+    {
+        return None;
+    }
+
+    let mut p = p.clone();
+    let mut antiderivative = h.clone();
+    let mut terms = Vec::new();
+
+    for k in 0..MAX_PARTS_STEPS {
+        antiderivative = fn_integrate(&antiderivative, var)?;
+        let term = fn_mul(p.clone(), antiderivative.clone());
+        terms.push(if k % 2 == 0 { term } else { fn_neg(term) });
+
+        if !p.depends_on(var) {
+            return Some(fn_sum(terms));
+        }
+        p = fn_pdv(&p, var).simplify();
+    }
+    None
+}
+
+/// true if f is a polynomial in x_var (with coefficients in the other variables)
+fn is_polynomial(f: &Function, var: usize) -> bool {
+    match f {
+        _ if !f.depends_on(var) => true,
+        Function::Var(_) | Function::Poly(_) => true,
+        Function::Add(f1, f2) | Function::Sub(f1, f2) | Function::Mul(f1, f2) => {
+            is_polynomial(f1, var) && is_polynomial(f2, var)
+        }
+        Function::Neg(g) => is_polynomial(g, var),
+        Function::PowI(g, n) => *n >= 0 && is_polynomial(g, var),
+        Function::Sum(fs) | Function::Prod(fs) | Function::PolyF(fs, _) => {
+            fs.iter().all(|g| is_polynomial(g, var))
+        }
+        _ => false,
+    }
+}
+
+/// returns log|u| as log(u^2) / 2
+fn log_abs(u: Function) -> Function {
+    fn_mul(fn_const(0.5), fn_log(fn_powi(u, 2)))
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::func::{fn_tan, X, Y};
+
+    #[test]
+    fn test_integrate() {
+        let linear = fn_add(fn_mul(fn_const(3.0), X), fn_const(1.0));
+        let functions = [
+            // polynomials and linearity
+            fn_poly(vec![1.0, -2.0, 0.5, 0.25]),
+            Function::PolyF(vec![fn_sin(Y), fn_const(2.0), Y], 0),
+            Function::PolyF(vec![fn_sin(X), X], 1),
+            fn_sub(fn_neg(fn_powi(X, 3)), fn_mul(Y, X)),
+            fn_sum(vec![X, fn_const(2.0), fn_div(X, fn_const(4.0))]),
+            // table with linear arguments
+            fn_sin(linear.clone()),
+            fn_cos(linear.clone()),
+            fn_tan(linear.clone()),
+            fn_mul(fn_const(2.0), fn_exp(linear.clone())),
+            fn_log(linear.clone()),
+            fn_powi(linear.clone(), -3),
+            fn_div(fn_const(1.0), linear.clone()),
+            // u-substitution
+            fn_mul(fn_mul(fn_const(2.0), X), fn_exp(fn_powi(X, 2))),
+            fn_div(X, fn_add(fn_powi(X, 2), fn_const(1.0))),
+            fn_mul(fn_sin(X), fn_cos(X)),
+            fn_div(fn_cos(X), fn_sin(X)),
+            fn_mul(fn_poly(vec![0.0, 0.0, 3.0]), fn_cos(fn_powi(X, 3))),
+            // by parts
+            fn_mul(X, fn_exp(X)),
+            fn_mul(
+                fn_poly(vec![1.0, 0.0, 2.0]),
+                fn_sin(fn_mul(fn_const(2.0), X)),
+            ),
+            fn_prod(vec![X, Y, X, fn_cos(fn_sub(X, Y))]),
+            // partial fractions
+            fn_div(fn_const(1.0), fn_poly(vec![-1.0, 0.0, 1.0])),
+            fn_div(fn_powi(X, 3), fn_powi(fn_poly(vec![-1.0, 1.0]), 2)),
+            // (2x + 1)/(x^2 + x + 1) + 2x/(x^2 + 2)
+            fn_div(
+                fn_poly(vec![2.0, 6.0, 3.0, 4.0]),
+                fn_mul(fn_poly(vec![1.0, 1.0, 1.0]), fn_poly(vec![2.0, 0.0, 1.0])),
+            ),
+        ];
+
+        for f in &functions {
+            let antiderivative =
+                fn_integrate(f, 0).unwrap_or_else(|| panic!("no integral for {f}"));
+            assert!(
+                fn_pdv(&antiderivative, 0).is_equivalent(f),
+                "d/dx {antiderivative} != {f}"
+            );
+        }
+
+        // with respect to another variable
+        let f = fn_mul(fn_exp(X), fn_cos(Y));
+        assert!(fn_pdv(&fn_integrate(&f, 1).unwrap(), 1).is_equivalent(&f));
+
+        // not elementary, or out of reach of the rules
+        for f in [
+            fn_exp(fn_powi(X, 2)),
+            fn_div(fn_sin(X), X),
+            fn_div(fn_const(1.0), fn_log(X)),
+            fn_mul(fn_exp(X), fn_sin(X)),
+            // arctan
+            fn_div(fn_const(1.0), fn_poly(vec![1.0, 0.0, 1.0])),
+        ] {
+            assert_eq!(fn_integrate(&f, 0), None, "{f}");
+        }
+    }
+}
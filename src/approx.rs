@@ -1,17 +1,17 @@
-use std::vec;
-
-// -----------------------------------------------------------------------------
-
 use crate::{
+    autodiff::Tape,
     func::*,
     integration::{int_inner_product, IntMethod},
-    polynomial::{get_legendre_rodrigues, poly_eval},
+    polynomial::get_legendre_rodrigues,
     util::sample_interval_random,
 };
+use ndarray::ArrayView2;
 
 // =============================================================================
 
-/// returns a new polynomial that is the result of one step of gradient descent
+/// performs one step of gradient descent on the coefficients, moving them to
+/// reduce the squared error against f on a random sample of the interval
+#[allow(dead_code)]
 pub fn compute_gradient_descent_step(
     f: &Function,
     coeffs: &mut [f64],
@@ -22,30 +22,27 @@ pub fn compute_gradient_descent_step(
     let xs = sample_interval_random(interval, sample_size);
     let grad = average_error_gradient(f, coeffs, &xs);
 
-    for k in 0..coeffs.len() {
-        coeffs[k] += step_size * grad[k];
+    for (c, g) in coeffs.iter_mut().zip(grad) {
+        *c -= step_size * g;
     }
 }
 
-/// returns the gradient of the error function e = (p - f)^2 averaged over a
-/// random sample of the interval
+/// returns the gradient with respect to the coefficients of the error
+/// e = (p - f)^2 / 2 averaged over the sample points
 fn average_error_gradient(f: &Function, coeffs: &[f64], xs: &[f64]) -> Vec<f64> {
-    let degree = coeffs.len() - 1;
-    let mut grad = vec![0.0; coeffs.len()];
-
-    for &x in xs {
-        let fx = f.eval([x]);
-        let px = poly_eval(coeffs, x);
-
-        for k in 0..degree {
-            // c = [c_0, c_1, ..., c_d]
-            // d/dc_k (f(x) - p_c(x))^2 = 2(p_c(x) - f(x)) * x^k
-            // just ignore the 2 and it gets sucked into learning rate i guess
-            grad[k] += (fx - px) * x.powi(k as i32) / (xs.len() as f64);
-        }
-    }
-
-    grad
+    let tape = Tape::new();
+    let cs = tape.vars(coeffs);
+
+    let errors = xs.iter().map(|&x| {
+        // p_c(x) by horner's method
+        let px = cs.iter().rev().fold(tape.var(0.0), |acc, &c| acc * x + c);
+        let diff = px - f.eval([x]);
+        diff * diff * 0.5
+    });
+    let loss = tape.sum(errors) / (xs.len() as f64);
+
+    let grads = loss.backward();
+    cs.iter().map(|&c| grads.wrt(c)).collect()
 }
 
 // =============================================================================
@@ -74,4 +71,47 @@ pub fn compute_legendre_approx(f: &Function, n: usize, int_method: IntMethod) ->
     p
 }
 
+/// mean of the squared differences between a and b over the rows of `points`
+pub fn mean_squared_error<A: EvaluateMany, B: EvaluateMany>(
+    a: &A,
+    b: &B,
+    points: &ArrayView2<f64>,
+) -> f64 {
+    let diff = a.eval_batch(points) - b.eval_batch(points);
+    diff.mapv(|d| d * d).mean().unwrap_or(0.0)
+}
+
 // =============================================================================
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_average_error_gradient() {
+        // p = 0 against f = x^2: d/dc_k = mean(-x^2 * x^k), for every k up to
+        // and including the leading coefficient
+        let f = fn_powi(X, 2);
+        let xs = [1.0, 2.0];
+        let grad = average_error_gradient(&f, &[0.0, 0.0, 0.0], &xs);
+
+        let expected = [-2.5, -4.5, -8.5];
+        assert_eq!(grad.len(), expected.len());
+        for (g, e) in grad.iter().zip(expected) {
+            assert!((g - e).abs() < 1e-12, "{grad:?}");
+        }
+    }
+
+    #[test]
+    fn test_mean_squared_error() {
+        let points = ndarray::array![[-1.0], [0.0], [2.0]];
+        let f = fn_powi(X, 2);
+        assert_eq!(mean_squared_error(&f, &f, &points.view()), 0.0);
+
+        // (x^2 - x)^2 at -1, 0, 2 is 4, 0, 4
+        let error = mean_squared_error(&f, &X, &points.view());
+        assert!((error - 8.0 / 3.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,373 @@
+use std::cell::RefCell;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// -----------------------------------------------------------------------------
+
+use crate::scalar::Scalar;
+
+// =============================================================================
+
+/// records every operation on its `Var`s so the gradient of a final result
+/// with respect to all of them can be computed in one backward sweep
+/// (reverse-mode automatic differentiation)
+///
+/// meant to be short lived: build one per loss evaluation and drop it after
+/// reading the gradients
+#[derive(Debug, Default)]
+pub struct Tape {
+    nodes: RefCell<Vec<Node>>,
+}
+
+/// an operation on the tape, with the local partial derivative of its result
+/// with respect to each of (at most two) operands
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    partials: [(usize, f64); 2],
+}
+
+/// a scalar value recorded on a tape
+///
+/// constants made through `Var::constant` (as `Function::eval` does via
+/// `Scalar::from_f64`) are off the tape: they have no node and every
+/// derivative with respect to them is zero
+#[derive(Debug, Clone, Copy)]
+pub struct Var<'t> {
+    tape: Option<&'t Tape>,
+    index: usize,
+    value: f64,
+}
+
+/// index of a var that is not on any tape
+const UNTAPED: usize = usize::MAX;
+
+impl Tape {
+    pub fn new() -> Self {
+        Tape::default()
+    }
+
+    /// records an input (a parameter or a constant)
+    pub fn var(&self, value: f64) -> Var<'_> {
+        self.push(value, [(0, 0.0), (0, 0.0)])
+    }
+
+    pub fn vars(&self, values: &[f64]) -> Vec<Var<'_>> {
+        values.iter().map(|&v| self.var(v)).collect()
+    }
+
+    /// sum of the terms, zero if there are none
+    pub fn sum<'t>(&'t self, terms: impl IntoIterator<Item = Var<'t>>) -> Var<'t> {
+        terms.into_iter().fold(self.var(0.0), |acc, v| acc + v)
+    }
+
+    /// dot product of two equally long vectors
+    pub fn dot<'t>(&'t self, a: &[Var<'t>], b: &[Var<'t>]) -> Var<'t> {
+        self.sum(a.iter().zip(b).map(|(&x, &y)| x * y))
+    }
+
+    fn push(&self, value: f64, partials: [(usize, f64); 2]) -> Var<'_> {
+        let mut nodes = self.nodes.borrow_mut();
+        nodes.push(Node { partials });
+        Var {
+            tape: Some(self),
+            index: nodes.len() - 1,
+            value,
+        }
+    }
+}
+
+// gradients -------------------------------------------------------------------
+
+/// derivatives of one output with respect to every var on the tape
+#[derive(Debug, Clone)]
+pub struct Gradients(Vec<f64>);
+
+impl Gradients {
+    /// d(output)/d(var), zero for vars the output doesn't depend on
+    pub fn wrt(&self, var: Var) -> f64 {
+        self.0.get(var.index).copied().unwrap_or(0.0)
+    }
+}
+
+impl<'t> Var<'t> {
+    /// a constant that is not recorded on any tape
+    pub fn constant(value: f64) -> Self {
+        Var {
+            tape: None,
+            index: UNTAPED,
+            value,
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// computes the gradient of this var with respect to all earlier vars
+    pub fn backward(&self) -> Gradients {
+        let Some(tape) = self.tape else {
+            return Gradients(Vec::new());
+        };
+        let nodes = tape.nodes.borrow();
+
+        let mut grads = vec![0.0; self.index + 1];
+        grads[self.index] = 1.0;
+
+        // nodes only depend on earlier nodes, so one reverse sweep suffices
+        for i in (0..=self.index).rev() {
+            let g = grads[i];
+            if g == 0.0 {
+                continue;
+            }
+            for (j, partial) in nodes[i].partials {
+                grads[j] += g * partial;
+            }
+        }
+
+        Gradients(grads)
+    }
+
+    /// applies an elementwise function given its value and derivative at
+    /// self, for functions not covered by the methods below
+    ///
+    /// records a node for the result, unless self is a constant
+    pub fn unary(self, value: f64, derivative: f64) -> Self {
+        match self.tape {
+            Some(tape) => tape.push(value, [(self.index, derivative), (self.index, 0.0)]),
+            None => Var::constant(value),
+        }
+    }
+
+    /// records a node for the result, constant operands contribute no
+    /// partial (and two constants give a constant)
+    fn binary(self, other: Self, value: f64, d_self: f64, d_other: f64) -> Self {
+        match (self.tape, other.tape) {
+            (Some(tape), Some(other_tape)) => {
+                assert!(std::ptr::eq(tape, other_tape), "vars from different tapes");
+                tape.push(value, [(self.index, d_self), (other.index, d_other)])
+            }
+            (Some(_), None) => self.unary(value, d_self),
+            (None, Some(_)) => other.unary(value, d_other),
+            (None, None) => Var::constant(value),
+        }
+    }
+
+    pub fn sin(self) -> Self {
+        self.unary(self.value.sin(), self.value.cos())
+    }
+
+    pub fn cos(self) -> Self {
+        self.unary(self.value.cos(), -self.value.sin())
+    }
+
+    pub fn tan(self) -> Self {
+        let t = self.value.tan();
+        self.unary(t, 1.0 + t * t)
+    }
+
+    pub fn exp(self) -> Self {
+        let e = self.value.exp();
+        self.unary(e, e)
+    }
+
+    pub fn ln(self) -> Self {
+        self.unary(self.value.ln(), 1.0 / self.value)
+    }
+
+    pub fn powi(self, n: i32) -> Self {
+        let df = if n == 0 {
+            0.0
+        } else {
+            n as f64 * self.value.powi(n - 1)
+        };
+        self.unary(self.value.powi(n), df)
+    }
+}
+
+// arithmetic ------------------------------------------------------------------
+
+impl<'t> Add for Var<'t> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.binary(other, self.value + other.value, 1.0, 1.0)
+    }
+}
+
+impl<'t> Sub for Var<'t> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.binary(other, self.value - other.value, 1.0, -1.0)
+    }
+}
+
+impl<'t> Mul for Var<'t> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        self.binary(other, self.value * other.value, other.value, self.value)
+    }
+}
+
+impl<'t> Div for Var<'t> {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let value = self.value / other.value;
+        self.binary(other, value, 1.0 / other.value, -value / other.value)
+    }
+}
+
+impl<'t> Neg for Var<'t> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.unary(-self.value, -1.0)
+    }
+}
+
+// mixing with plain numbers, only the result is recorded
+
+impl<'t> Add<f64> for Var<'t> {
+    type Output = Self;
+
+    fn add(self, c: f64) -> Self {
+        self.unary(self.value + c, 1.0)
+    }
+}
+
+impl<'t> Sub<f64> for Var<'t> {
+    type Output = Self;
+
+    fn sub(self, c: f64) -> Self {
+        self.unary(self.value - c, 1.0)
+    }
+}
+
+impl<'t> Mul<f64> for Var<'t> {
+    type Output = Self;
+
+    fn mul(self, c: f64) -> Self {
+        self.unary(self.value * c, c)
+    }
+}
+
+impl<'t> Div<f64> for Var<'t> {
+    type Output = Self;
+
+    fn div(self, c: f64) -> Self {
+        self.unary(self.value / c, 1.0 / c)
+    }
+}
+
+// evaluation ------------------------------------------------------------------
+
+/// lets `Function::eval` record onto the tape of its arguments
+impl<'t> Scalar for Var<'t> {
+    fn from_f64(c: f64) -> Self {
+        Var::constant(c)
+    }
+
+    fn sin(self) -> Self {
+        Var::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        Var::cos(self)
+    }
+
+    fn tan(self) -> Self {
+        Var::tan(self)
+    }
+
+    fn exp(self) -> Self {
+        Var::exp(self)
+    }
+
+    fn ln(self) -> Self {
+        Var::ln(self)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        Var::powi(self, n)
+    }
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backward() {
+        let tape = Tape::new();
+        let x = tape.var(0.5);
+        let y = tape.var(2.0);
+
+        // f(x, y) = sin(x*y) + x^3 / y - exp(x) * 4
+        let f = (x * y).sin() + x.powi(3) / y - x.exp() * 4.0;
+        let grads = f.backward();
+
+        let (xv, yv): (f64, f64) = (0.5, 2.0);
+        let dfdx = yv * (xv * yv).cos() + 3.0 * xv * xv / yv - 4.0 * xv.exp();
+        let dfdy = xv * (xv * yv).cos() - xv.powi(3) / (yv * yv);
+
+        assert!((grads.wrt(x) - dfdx).abs() < 1e-12);
+        assert!((grads.wrt(y) - dfdy).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_shared_subexpression() {
+        // f = u * u with u = x + 1, so df/dx = 2(x + 1)
+        let tape = Tape::new();
+        let x = tape.var(3.0);
+        let u = x + 1.0;
+        let f = u * u;
+
+        assert_eq!(f.value(), 16.0);
+        assert_eq!(f.backward().wrt(x), 8.0);
+
+        let w = tape.vars(&[1.0, 2.0]);
+        let v = tape.vars(&[3.0, 4.0]);
+        let d = tape.dot(&w, &v);
+        let grads = d.backward();
+        assert_eq!(d.value(), 11.0);
+        assert_eq!((grads.wrt(w[0]), grads.wrt(w[1])), (3.0, 4.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "vars from different tapes")]
+    fn test_mixed_tapes() {
+        let (a, b) = (Tape::new(), Tape::new());
+        let _ = a.var(1.0) + b.var(2.0);
+    }
+
+    #[test]
+    fn test_function_on_tape() {
+        use crate::func::*;
+
+        // f(x, y) = 2 sin(x y) + x^3 / y - 4, whose constants stay off the tape
+        let f = fn_sum(vec![
+            fn_mul(fn_const(2.0), fn_sin(fn_mul(X, Y))),
+            fn_div(fn_powi(X, 3), Y),
+            fn_const(-4.0),
+        ]);
+
+        let tape = Tape::new();
+        let args = tape.vars(&[0.5, 2.0]);
+        let y: Var = f.eval(&args);
+        let grads = y.backward();
+
+        let (value, gradient) = f.eval_with_gradient([0.5, 2.0]);
+        assert!((y.value() - value).abs() < 1e-12);
+        assert!((grads.wrt(args[0]) - gradient[0]).abs() < 1e-12);
+        assert!((grads.wrt(args[1]) - gradient[1]).abs() < 1e-12);
+
+        // constants never reach the tape
+        let c = Var::constant(3.0) * Var::constant(2.0);
+        assert_eq!(c.value(), 6.0);
+        assert_eq!(grads.wrt(c), 0.0);
+        assert_eq!(c.backward().wrt(args[0]), 0.0);
+    }
+}
@@ -1,18 +1,79 @@
 use macroquad::{color, prelude::*};
+use num_complex::Complex64;
 
 // -----------------------------------------------------------------------------
 
+use crate::dag::{Dag, NodeId};
 use crate::func::Function;
+use crate::interval::{isolate_roots, Interval, RootEnclosure};
+use crate::parallel;
+use crate::parametric::ParametricCurve;
 use crate::util::{sample_interval_equidistributed, Point};
 use crate::EvaluateOne;
 
 // =============================================================================
 
+/// number of equidistant pieces adaptive sampling starts from
+const INITIAL_SAMPLES: usize = 64;
+
+/// how far (in pixels) the drawn polyline may stray from the true curve
+pub const PIXEL_TOLERANCE: f32 = 0.5;
+
+/// how many times a single initial piece may be bisected
+const MAX_REFINE_DEPTH: u32 = 10;
+
+/// rough distance (in pixels) between major grid lines
+const GRID_PIXEL_SPACING: f32 = 100.0;
+
+const GRID_MINOR_COLOR: Color = Color::new(0.9, 0.9, 0.9, 1.0);
+
+const LABEL_FONT_SIZE: u16 = 16;
+
+/// size (in pixels) of the blocks domain coloring evaluates once
+const DOMAIN_COLORING_PIXELS: f32 = 4.0;
+
+/// angle (in radians) between the spokes of the polar grid
+const POLAR_SPOKE_ANGLE: f64 = std::f64::consts::PI / 12.0;
+
+/// enclosures taller than this (in pixels) are tightened by bisecting the
+/// column they were computed for
+const ENCLOSURE_PIXEL_HEIGHT: f32 = 4.0;
+
+/// how many times a single pixel column may be bisected
+const ENCLOSURE_REFINE_DEPTH: u32 = 4;
+
+/// size (in pixels) of the coarse grid implicit curves are sampled on
+const IMPLICIT_CELL_PIXELS: f32 = 16.0;
+
+/// how many times a coarse cell containing a curve is split into four
+const IMPLICIT_REFINE_DEPTH: u32 = 3;
+
+/// rough number of level sets `draw_contour_plot` draws
+const CONTOUR_LEVELS: f32 = 8.0;
+
+/// radius (in pixels) of the markers `draw_roots` draws
+const ROOT_MARKER_RADIUS: f32 = 5.0;
+
+// =============================================================================
+
 #[derive(Debug)]
 pub struct Camera {
     pub center: Point,
     width: f64,
     height: f64,
+    /// draw dashed vertical lines at the known poles of plotted functions
+    pub show_asymptotes: bool,
+    /// draw expressions as guaranteed enclosures of their graphs instead of
+    /// sampled curves, see `draw_function_enclosure`
+    pub guaranteed: bool,
+    /// mark the roots of plotted expressions, see `draw_roots`
+    pub show_roots: bool,
+    /// draw the polar grid instead of the cartesian one, see `draw_polar_grid`
+    pub polar_grid: bool,
+    /// trace the graphs of expressions at the mouse, see `draw_tangent`
+    pub show_tangents: bool,
+    /// font for the axis labels, falls back to the macroquad default
+    font: Option<Font>,
 }
 
 impl Default for Camera {
@@ -29,6 +90,12 @@ impl Camera {
             center,
             width,
             height,
+            show_asymptotes: true,
+            guaranteed: false,
+            show_roots: false,
+            polar_grid: false,
+            show_tangents: false,
+            font: load_ttf_font_from_bytes(include_bytes!("../assets/cmunrm.ttf")).ok(),
         }
     }
 
@@ -52,12 +119,14 @@ impl Camera {
 
     // modify camera -----------------------------------------------------------
 
+    #[allow(dead_code)]
     pub fn reset(&mut self) {
         self.center = Point::origin();
         self.width = 20.0;
         self.set_aspect_ratio((screen_width() / screen_height()) as f64);
     }
 
+    #[allow(dead_code)]
     pub fn set_scale(&mut self, scale: f64) {
         self.width *= scale;
         self.height *= scale;
@@ -88,78 +157,488 @@ impl Camera {
 
     // drawing -----------------------------------------------------------------
 
+    /// draws minor and major grid lines, the axes, and labels at the major ticks
+    ///
+    /// the major spacing is a 1/2/5 * 10^k step chosen so lines stay roughly
+    /// `GRID_PIXEL_SPACING` apart at any zoom level
     pub fn draw_grid(&self) {
-        // vertical lines at x = n for integers n inside the camera width
+        let (x_major, x_minor) = grid_spacing(self.width, screen_width());
+        let (y_major, y_minor) = grid_spacing(self.height, screen_height());
+
+        // minor lines first so the major lines and axes draw over them
+        for x in grid_lines(self.left(), self.right(), x_minor) {
+            let x = self.euc_to_screen_x(x);
+            draw_line(x, 0.0, x, screen_height(), 1.0, GRID_MINOR_COLOR);
+        }
+        for y in grid_lines(self.bottom(), self.top(), y_minor) {
+            let y = self.euc_to_screen_y(y);
+            draw_line(0.0, y, screen_width(), y, 1.0, GRID_MINOR_COLOR);
+        }
+
+        for x in grid_lines(self.left(), self.right(), x_major) {
+            let x = self.euc_to_screen_x(x);
+            draw_line(x, 0.0, x, screen_height(), 1.0, GRAY);
+        }
+        for y in grid_lines(self.bottom(), self.top(), y_major) {
+            let y = self.euc_to_screen_y(y);
+            draw_line(0.0, y, screen_width(), y, 1.0, GRAY);
+        }
+
+        // axes
+        let x0 = self.euc_to_screen_x(0.0);
+        let y0 = self.euc_to_screen_y(0.0);
+        draw_line(x0, 0.0, x0, screen_height(), 2.0, BLACK);
+        draw_line(0.0, y0, screen_width(), y0, 2.0, BLACK);
+
+        self.draw_axis_labels(x_major, y_major);
+    }
 
-        let v_min = self.left().floor() as i32;
-        let v_max = self.right().ceil() as i32;
+    /// labels the major ticks next to the axes, pinned to the screen edges
+    /// when an axis is scrolled out of view
+    fn draw_axis_labels(&self, x_major: f64, y_major: f64) {
+        let margin = 4.0;
 
-        for v in v_min..=v_max {
-            let mut color = GRAY;
-            let mut stroke = 1.0;
+        // x labels hang below the x-axis
+        let y_axis = self.euc_to_screen_y(0.0);
+        for x in grid_lines(self.left(), self.right(), x_major) {
+            if x == 0.0 {
+                continue;
+            }
+            let text = format_tick(x, x_major);
+            let size = self.measure_label(&text);
+
+            let sx = self.euc_to_screen_x(x) - size.width / 2.0;
+            let sy = (y_axis + margin + size.offset_y)
+                .clamp(margin + size.offset_y, screen_height() - margin);
+            self.draw_label(&text, sx, sy);
+        }
 
-            if v == 0 {
-                color = BLACK;
-                stroke = 2.0;
+        // y labels sit to the left of the y-axis
+        let x_axis = self.euc_to_screen_x(0.0);
+        for y in grid_lines(self.bottom(), self.top(), y_major) {
+            if y == 0.0 {
+                continue;
             }
+            let text = format_tick(y, y_major);
+            let size = self.measure_label(&text);
 
-            let x = self.euc_to_screen_x(v as f64);
-            draw_line(x, 0.0, x, screen_height(), stroke, color);
+            let sx =
+                (x_axis - margin - size.width).clamp(margin, screen_width() - margin - size.width);
+            let sy = self.euc_to_screen_y(y) + size.offset_y / 2.0;
+            self.draw_label(&text, sx, sy);
         }
 
-        // horizontal lines at y = n for integers n inside the camera height
+        // origin
+        let size = self.measure_label("0");
+        let (sx, sy) = (
+            x_axis - margin - size.width,
+            y_axis + margin + size.offset_y,
+        );
+        if (0.0..screen_width()).contains(&sx) && (0.0..screen_height()).contains(&sy) {
+            self.draw_label("0", sx, sy);
+        }
+    }
 
-        let h_min = self.bottom().floor() as i32;
-        let h_max = self.top().ceil() as i32;
+    fn measure_label(&self, text: &str) -> TextDimensions {
+        measure_text(text, self.font.as_ref(), LABEL_FONT_SIZE, 1.0)
+    }
 
-        for h in h_min..=h_max {
-            let mut color = GRAY;
-            let mut stroke = 1.0;
+    fn draw_label(&self, text: &str, x: f32, y: f32) {
+        let params = TextParams {
+            font: self.font.as_ref(),
+            font_size: LABEL_FONT_SIZE,
+            color: DARKGRAY,
+            ..Default::default()
+        };
+        draw_text_ex(text, x, y, params);
+    }
+
+    /// draws a line of text in the bottom right corner, `line` counting up
+    /// from the bottom
+    pub fn draw_status(&self, line: usize, text: &str, color: Color) {
+        let size = self.measure_label(text);
+        let margin = 10.0;
+        let params = TextParams {
+            font: self.font.as_ref(),
+            font_size: LABEL_FONT_SIZE,
+            color,
+            ..Default::default()
+        };
+        let y = screen_height() - margin - line as f32 * (LABEL_FONT_SIZE as f32 + 4.0);
+        draw_text_ex(text, screen_width() - margin - size.width, y, params);
+    }
+
+    pub fn draw_function<F: EvaluateOne + Sync>(&self, f: &F, color: Color) {
+        let interval = (self.left(), self.right());
 
-            if h == 0 {
-                color = BLACK;
-                stroke = 2.0;
+        // sample between known poles separately, so no segment crosses one
+        let poles = f.singularities(interval);
+        let breaks: Vec<f64> = std::iter::once(interval.0)
+            .chain(poles.iter().copied())
+            .chain(std::iter::once(interval.1))
+            .collect();
+
+        for piece in breaks.windows(2) {
+            let segments = self
+                .viewport()
+                .sample(|x| Point::new(x, f.eval_one(x)), (piece[0], piece[1]));
+            draw_polylines(&segments, color);
+        }
+
+        if self.show_asymptotes {
+            for &x in &poles {
+                draw_dashed_vertical_line(self.euc_to_screen_x(x), color);
             }
+        }
+    }
 
-            let y = self.euc_to_screen_y(h as f64);
-            draw_line(0.0, y, screen_width(), y, stroke, color);
+    /// highlights the points (x, 0) for the roots x with ringed markers
+    pub fn draw_roots(&self, roots: &[f64], color: Color) {
+        let y = self.euc_to_screen_y(0.0);
+        for &root in roots {
+            let x = self.euc_to_screen_x(root);
+            draw_circle(x, y, ROOT_MARKER_RADIUS, WHITE);
+            draw_circle_lines(x, y, ROOT_MARKER_RADIUS, 2.0, color);
         }
     }
 
-    pub fn draw_function<F: EvaluateOne>(&self, f: &F, color: Color) {
-        let interval = (self.left(), self.right());
+    /// marks intervals at most a pixel wide that together contain every root
+    /// of f (in x_0) on screen, filled where a root is certain and outlined
+    /// where interval evaluation can't rule one out
+    pub fn draw_root_enclosures(&self, f: &Function, color: Color) {
+        let tol = self.width / screen_width() as f64;
+        let y = self.euc_to_screen_y(0.0) - ROOT_MARKER_RADIUS;
+        let height = 2.0 * ROOT_MARKER_RADIUS;
+
+        for RootEnclosure { interval, verified } in
+            isolate_roots(f, (self.left(), self.right()), tol)
+        {
+            let left = self.euc_to_screen_x(interval.lo);
+            let width = (self.euc_to_screen_x(interval.hi) - left).max(2.0);
+            if verified {
+                draw_rectangle(left, y, width, height, color);
+            } else {
+                draw_rectangle_lines(left, y, width, height, 2.0, color);
+            }
+        }
+    }
 
-        let xs = sample_interval_equidistributed(interval, 1000);
-        let samples: Vec<(f64, f64)> = xs.iter().map(|&x| (x, f.eval_one(x))).collect();
+    /// marks the point (x, y) of a graph and draws the tangent with the given
+    /// slope through it across the screen, labeled with the point and slope
+    pub fn draw_tangent(&self, x: f64, y: f64, slope: f64, color: Color) {
+        if !y.is_finite() || !slope.is_finite() {
+            return;
+        }
 
-        let screen_points: Vec<(f32, f32)> = samples
-            .iter()
-            .map(|p| self.euc_to_screen(Point::new(p.0, p.1)))
+        let at = |u: f64| self.euc_to_screen(Point::new(u, y + slope * (u - x)));
+        let (x1, y1) = at(self.left());
+        let (x2, y2) = at(self.right());
+        draw_line(x1, y1, x2, y2, 1.0, color);
+
+        let (px, py) = self.euc_to_screen(Point::new(x, y));
+        draw_circle(px, py, ROOT_MARKER_RADIUS, color);
+        let text = format!("({:.3}, {:.3})  slope {:.3}", x, y, slope);
+        self.draw_label(
+            &text,
+            px + 2.0 * ROOT_MARKER_RADIUS,
+            py - 2.0 * ROOT_MARKER_RADIUS,
+        );
+    }
+
+    /// draws a band guaranteed to contain the graph of f (in x_0)
+    ///
+    /// each pixel column is filled between the bounds interval evaluation
+    /// gives for f over the whole column, so no spike or oscillation can fall
+    /// between samples. columns with an enclosure taller than
+    /// `ENCLOSURE_PIXEL_HEIGHT` are bisected up to `ENCLOSURE_REFINE_DEPTH`
+    /// times to tighten the band. f is evaluated as a `Dag`, so repeated
+    /// subexpressions are enclosed once per column
+    pub fn draw_function_enclosure(&self, f: &Function, color: Color) {
+        let columns = screen_width().ceil() as usize;
+        let dx = self.width / screen_width() as f64;
+
+        let mut dag = Dag::new();
+        let root = dag.add_function(f);
+        for j in 0..columns {
+            let x = self.left() + dx * j as f64;
+            self.draw_enclosure_column(&dag, root, Interval::new(x, x + dx), 0, color);
+        }
+    }
+
+    fn draw_enclosure_column(
+        &self,
+        dag: &Dag,
+        root: NodeId,
+        x: Interval,
+        depth: u32,
+        color: Color,
+    ) {
+        let y = dag.eval(root, &[x]);
+
+        // nothing of the graph in this column is visible
+        if y.is_empty() || y.lo > self.top() || y.hi < self.bottom() {
+            return;
+        }
+
+        let top = self.euc_to_screen_y(y.hi.min(self.top()));
+        let bottom = self.euc_to_screen_y(y.lo.max(self.bottom()));
+
+        if bottom - top > ENCLOSURE_PIXEL_HEIGHT && depth < ENCLOSURE_REFINE_DEPTH {
+            let (left, right) = x.bisect();
+            self.draw_enclosure_column(dag, root, left, depth + 1, color);
+            self.draw_enclosure_column(dag, root, right, depth + 1, color);
+            return;
+        }
+
+        let left = self.euc_to_screen_x(x.lo);
+        let right = self.euc_to_screen_x(x.hi);
+        // at least a pixel, so thin pieces stay visible
+        let height = (bottom - top).max(1.0);
+        draw_rectangle(left, top, (right - left).max(1.0), height, color);
+    }
+
+    /// draws the curve over its parameter range, sampling adaptively in t
+    pub fn draw_parametric(&self, curve: &ParametricCurve, color: Color) {
+        let segments = curve.sample(&self.viewport());
+        draw_polylines(&segments, color);
+    }
+
+    // polar -------------------------------------------------------------------
+
+    /// draws the polar graph r = f(theta) for theta in `theta_range`, where
+    /// theta is the variable x_0 of f
+    pub fn draw_polar(&self, f: &Function, theta_range: (f64, f64), color: Color) {
+        let segments = self.viewport().sample(
+            |theta| Point::from_polar(f.eval([theta]), theta),
+            theta_range,
+        );
+        draw_polylines(&segments, color);
+    }
+
+    /// draws concentric circles around the origin at the major grid spacing
+    /// and spokes every `POLAR_SPOKE_ANGLE`, an alternative to `draw_grid`
+    pub fn draw_polar_grid(&self) {
+        let (major, _) = grid_spacing(self.width, screen_width());
+        let pixels_per_unit = screen_width() as f64 / self.width;
+
+        // range of distances from the origin covered by the screen
+        let r_min = Point::new(
+            0.0f64.clamp(self.left(), self.right()),
+            0.0f64.clamp(self.bottom(), self.top()),
+        )
+        .to_polar()
+        .0;
+        let r_max = [
+            (self.left(), self.bottom()),
+            (self.left(), self.top()),
+            (self.right(), self.bottom()),
+            (self.right(), self.top()),
+        ]
+        .iter()
+        .map(|&(x, y)| x.hypot(y))
+        .fold(0.0, f64::max);
+
+        let (x0, y0) = self.euc_to_screen(Point::origin());
+
+        for r in grid_lines(r_min, r_max, major) {
+            if r == 0.0 {
+                continue;
+            }
+            let radius = (r * pixels_per_unit) as f32;
+            draw_circle_lines(x0, y0, radius, 1.0, GRAY);
+
+            // label where the circle meets the positive x-axis
+            let text = format_tick(r, major);
+            let size = self.measure_label(&text);
+            self.draw_label(&text, x0 + radius + 4.0, y0 + size.offset_y + 4.0);
+        }
+
+        let spokes = (std::f64::consts::TAU / POLAR_SPOKE_ANGLE).round() as usize;
+        for k in 0..spokes {
+            let end = self.euc_to_screen(Point::from_polar(r_max, k as f64 * POLAR_SPOKE_ANGLE));
+            let (color, stroke) = if k % (spokes / 4) == 0 {
+                (BLACK, 2.0)
+            } else {
+                (GRAY, 1.0)
+            };
+            draw_line(x0, y0, end.0, end.1, stroke, color);
+        }
+    }
+
+    // complex -----------------------------------------------------------------
+
+    /// shades the screen by the value of f(z), z = x + iy being the variable x_0
+    ///
+    /// the hue gives the argument of f(z) (red on the positive real axis) and
+    /// the lightness its magnitude, from black at zeros to white at poles.
+    /// f is evaluated once per block of `DOMAIN_COLORING_PIXELS` pixels
+    pub fn draw_domain_coloring(&self, f: &Function) {
+        let cols = (screen_width() / DOMAIN_COLORING_PIXELS).ceil() as u16;
+        let rows = (screen_height() / DOMAIN_COLORING_PIXELS).ceil() as u16;
+
+        let mut image = Image::gen_image_color(cols, rows, WHITE);
+        for i in 0..rows {
+            for j in 0..cols {
+                // center of the block
+                let p = self.screen_to_euc((
+                    (j as f32 + 0.5) * DOMAIN_COLORING_PIXELS,
+                    (i as f32 + 0.5) * DOMAIN_COLORING_PIXELS,
+                ));
+                let w = f.eval_complex([Complex64::new(p.x, p.y)]);
+                image.set_pixel(j as u32, i as u32, domain_color(w));
+            }
+        }
+
+        let texture = Texture2D::from_image(&image);
+        texture.set_filter(FilterMode::Nearest);
+
+        let params = DrawTextureParams {
+            dest_size: Some(vec2(
+                cols as f32 * DOMAIN_COLORING_PIXELS,
+                rows as f32 * DOMAIN_COLORING_PIXELS,
+            )),
+            ..Default::default()
+        };
+        draw_texture_ex(&texture, 0.0, 0.0, WHITE, params);
+    }
+
+    /// marks points of the complex plane (as z = x + iy), zeros filled and
+    /// poles outlined
+    pub fn draw_zeros_and_poles(&self, zeros: &[Complex64], poles: &[Complex64]) {
+        for z in zeros {
+            let (x, y) = self.euc_to_screen(Point::new(z.re, z.im));
+            draw_circle(x, y, ROOT_MARKER_RADIUS, WHITE);
+            draw_circle_lines(x, y, ROOT_MARKER_RADIUS, 2.0, BLACK);
+        }
+        for z in poles {
+            let (x, y) = self.euc_to_screen(Point::new(z.re, z.im));
+            draw_circle_lines(x, y, ROOT_MARKER_RADIUS, 2.0, BLACK);
+        }
+    }
+
+    // implicit curves ---------------------------------------------------------
+
+    /// draws the zero set {(x, y) : f(x, y) = 0} of f, with x = x_0 and y = x_1
+    pub fn draw_implicit(&self, f: &Function, color: Color) {
+        self.draw_contours(f, &[0.0], color);
+    }
+
+    /// draws the level sets {(x, y) : f(x, y) = c} for each c in `levels`
+    ///
+    /// f is sampled on a grid of `IMPLICIT_CELL_PIXELS` sized cells, cells
+    /// where f - c changes sign are subdivided `IMPLICIT_REFINE_DEPTH` times
+    /// and the curve is extracted from the smallest cells by marching squares
+    pub fn draw_contours(&self, f: &Function, levels: &[f64], color: Color) {
+        let eval = |x: f64, y: f64| f.eval([x, y]);
+
+        let cols = (screen_width() / IMPLICIT_CELL_PIXELS).ceil() as usize;
+        let rows = (screen_height() / IMPLICIT_CELL_PIXELS).ceil() as usize;
+        let dx = self.width * (IMPLICIT_CELL_PIXELS / screen_width()) as f64;
+        let dy = self.height * (IMPLICIT_CELL_PIXELS / screen_height()) as f64;
+
+        // values at the grid corners, row 0 at the bottom of the screen
+        let values: Vec<Vec<f64>> = (0..=rows)
+            .map(|i| {
+                let y = self.bottom() + dy * i as f64;
+                (0..=cols)
+                    .map(|j| eval(self.left() + dx * j as f64, y))
+                    .collect()
+            })
             .collect();
 
-        for pair in screen_points.windows(2) {
-            let (x1, y1) = pair[0];
-            let (x2, y2) = pair[1];
+        for &level in levels {
+            for i in 0..rows {
+                for j in 0..cols {
+                    let cell = Cell {
+                        x: self.left() + dx * j as f64,
+                        y: self.bottom() + dy * i as f64,
+                        w: dx,
+                        h: dy,
+                        corners: [
+                            values[i][j] - level,
+                            values[i][j + 1] - level,
+                            values[i + 1][j + 1] - level,
+                            values[i + 1][j] - level,
+                        ],
+                    };
+                    self.contour_cell(&|x, y| eval(x, y) - level, &cell, 0, color);
+                }
+            }
+        }
+    }
 
-            draw_line(x1, y1, x2, y2, 3.0, color);
+    /// draws level sets of f at round values spread over the range f takes on
+    /// screen, about `CONTOUR_LEVELS` of them
+    pub fn draw_contour_plot(&self, f: &Function, color: Color) {
+        let cols = (screen_width() / IMPLICIT_CELL_PIXELS).ceil() as usize;
+        let rows = (screen_height() / IMPLICIT_CELL_PIXELS).ceil() as usize;
+
+        let (min, max) = (0..=rows)
+            .flat_map(|i| (0..=cols).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                f.eval([
+                    self.left() + self.width * j as f64 / cols as f64,
+                    self.bottom() + self.height * i as f64 / rows as f64,
+                ])
+            })
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+        if min >= max {
+            return;
+        }
+
+        let (step, _) = grid_spacing(max - min, CONTOUR_LEVELS * GRID_PIXEL_SPACING);
+        let levels: Vec<f64> = grid_lines(min, max, step).collect();
+        self.draw_contours(f, &levels, color);
+    }
+
+    /// refines a cell containing a sign change and draws the curve through it
+    fn contour_cell<E: Fn(f64, f64) -> f64>(&self, g: &E, cell: &Cell, depth: u32, color: Color) {
+        if !cell.has_sign_change() {
+            return;
+        }
+
+        if depth < IMPLICIT_REFINE_DEPTH {
+            for sub in cell.subdivide(g) {
+                self.contour_cell(g, &sub, depth + 1, color);
+            }
+            return;
+        }
+
+        let center = g(cell.x + cell.w / 2.0, cell.y + cell.h / 2.0);
+        for (p, q) in cell.marching_squares(center) {
+            let (x1, y1) = self.euc_to_screen(p);
+            let (x2, y2) = self.euc_to_screen(q);
+            draw_line(x1, y1, x2, y2, 2.0, color);
         }
     }
 
     // computations ------------------------------------------------------------
 
+    /// the current mapping to the screen, as plain data other threads can use
+    pub fn viewport(&self) -> Viewport {
+        Viewport {
+            left: self.left(),
+            top: self.top(),
+            width: self.width,
+            height: self.height,
+            screen_width: screen_width(),
+            screen_height: screen_height(),
+        }
+    }
+
     fn euc_to_screen_x(&self, x: f64) -> f32 {
-        // (x - left) + screen_width / cam_width
-        // don't know why x is negative.
-        ((x - self.left()) * (screen_width() as f64) / self.width) as f32
+        self.viewport().euc_to_screen_x(x)
     }
 
     fn euc_to_screen_y(&self, y: f64) -> f32 {
-        // -(y - top) + screen_height / cam_height
-        (-(y - self.top()) * (screen_height() as f64) / self.height) as f32
+        self.viewport().euc_to_screen_y(y)
     }
 
     fn euc_to_screen(&self, p: Point) -> (f32, f32) {
-        (self.euc_to_screen_x(p.x), self.euc_to_screen_y(p.y))
+        self.viewport().euc_to_screen(p)
     }
 
     pub fn screen_to_euc(&self, p: (f32, f32)) -> Point {
@@ -169,3 +648,375 @@ impl Camera {
         Point::new(x, y)
     }
 }
+
+// =============================================================================
+
+/// camera rectangle and screen size captured once per curve, since
+/// macroquad's screen queries may only be used from the main thread
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+    pub screen_width: f32,
+    pub screen_height: f32,
+}
+
+impl Viewport {
+    /// samples the curve t -> p(t) over the interval and returns it in screen
+    /// coordinates, split into connected segments
+    ///
+    /// starts from a coarse equidistant grid and recursively bisects any piece
+    /// whose midpoint lies more than `PIXEL_TOLERANCE` away from the chord, so
+    /// samples pile up only where the curve bends on screen. the curve is
+    /// broken wherever it is not finite or still jumps after full refinement.
+    /// the pieces of the grid are refined independently (on the thread pool
+    /// with the `parallel` feature) and joined in order
+    pub fn sample<P: Fn(f64) -> Point + Sync>(
+        &self,
+        curve: P,
+        interval: (f64, f64),
+    ) -> Vec<Vec<(f32, f32)>> {
+        let ts = sample_interval_equidistributed(interval, INITIAL_SAMPLES);
+
+        let pieces = parallel::map_indexed(ts.len() - 1, |i| {
+            let a = (ts[i], self.euc_to_screen(curve(ts[i])));
+            let b = (ts[i + 1], self.euc_to_screen(curve(ts[i + 1])));
+
+            let mut points = Vec::new();
+            if i == 0 && is_finite(a.1) {
+                points.push(a.1);
+            }
+            self.refine(&curve, a, b, 0, &mut points);
+            points
+        });
+
+        // non-finite points mark where the curve is broken
+        let mut segments: Vec<Vec<(f32, f32)>> = pieces
+            .concat()
+            .split(|&p| !is_finite(p))
+            .map(<[(f32, f32)]>::to_vec)
+            .collect();
+        segments.retain(|segment| segment.len() > 1);
+        segments
+    }
+
+    fn euc_to_screen_x(&self, x: f64) -> f32 {
+        // (x - left) + screen_width / cam_width
+        // don't know why x is negative.
+        ((x - self.left) * (self.screen_width as f64) / self.width) as f32
+    }
+
+    fn euc_to_screen_y(&self, y: f64) -> f32 {
+        // -(y - top) + screen_height / cam_height
+        (-(y - self.top) * (self.screen_height as f64) / self.height) as f32
+    }
+
+    pub fn euc_to_screen(&self, p: Point) -> (f32, f32) {
+        (self.euc_to_screen_x(p.x), self.euc_to_screen_y(p.y))
+    }
+
+    /// pushes the samples strictly after `a` up to and including `b`,
+    /// subdividing [a, b] while the screen-space error is too large. a
+    /// non-finite point is pushed where the curve is broken
+    fn refine<P: Fn(f64) -> Point>(
+        &self,
+        curve: &P,
+        a: (f64, (f32, f32)),
+        b: (f64, (f32, f32)),
+        depth: u32,
+        points: &mut Vec<(f32, f32)>,
+    ) {
+        if depth < MAX_REFINE_DEPTH {
+            let t = (a.0 + b.0) / 2.0;
+            let m = (t, self.euc_to_screen(curve(t)));
+
+            if self.needs_refinement(a.1, m.1, b.1) {
+                self.refine(curve, a, m, depth + 1, points);
+                self.refine(curve, m, b, depth + 1, points);
+                return;
+            }
+        }
+
+        // a and b are joined unless the curve is undefined at either end, or
+        // they are still far apart after refining as far as allowed
+        let broken = !is_finite(a.1)
+            || !is_finite(b.1)
+            || (depth >= MAX_REFINE_DEPTH && (a.1 .1 - b.1 .1).abs() > self.screen_height);
+
+        if broken {
+            points.push((f32::NAN, f32::NAN));
+        }
+        if is_finite(b.1) {
+            points.push(b.1);
+        }
+    }
+
+    /// decides whether the piece from p through q to r should be bisected
+    ///
+    /// pieces that are partly undefined are refined to locate the edge of the
+    /// domain, entirely undefined ones are dropped
+    fn needs_refinement(&self, p: (f32, f32), q: (f32, f32), r: (f32, f32)) -> bool {
+        match (is_finite(p), is_finite(q), is_finite(r)) {
+            (true, true, true) => {
+                !self.is_offscreen_segment(p, q, r)
+                    && distance_to_segment(q, p, r) > PIXEL_TOLERANCE
+            }
+            (false, false, false) => false,
+            _ => true,
+        }
+    }
+
+    /// true if all the points lie beyond the same edge of the screen, in which
+    /// case nothing of the piece between them would be visible anyway
+    fn is_offscreen_segment(&self, p: (f32, f32), q: (f32, f32), r: (f32, f32)) -> bool {
+        let (w, h) = (self.screen_width, self.screen_height);
+        (p.1 < 0.0 && q.1 < 0.0 && r.1 < 0.0)
+            || (p.1 > h && q.1 > h && r.1 > h)
+            || (p.0 < 0.0 && q.0 < 0.0 && r.0 < 0.0)
+            || (p.0 > w && q.0 > w && r.0 > w)
+    }
+}
+
+/// axis aligned cell of the marching squares grid, with the values of the
+/// sampled function at its corners in the order
+/// (bottom left, bottom right, top right, top left)
+struct Cell {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    corners: [f64; 4],
+}
+
+impl Cell {
+    /// true if the corners are all defined and not all of the same sign
+    fn has_sign_change(&self) -> bool {
+        self.corners.iter().all(|v| v.is_finite())
+            && self.corners.iter().any(|&v| v > 0.0)
+            && self.corners.iter().any(|&v| v <= 0.0)
+    }
+
+    /// splits the cell into four, evaluating g at the five new grid points
+    fn subdivide<E: Fn(f64, f64) -> f64>(&self, g: &E) -> [Cell; 4] {
+        let (w, h) = (self.w / 2.0, self.h / 2.0);
+        let [bl, br, tr, tl] = self.corners;
+
+        let b = g(self.x + w, self.y);
+        let r = g(self.x + self.w, self.y + h);
+        let t = g(self.x + w, self.y + self.h);
+        let l = g(self.x, self.y + h);
+        let c = g(self.x + w, self.y + h);
+
+        let cell = |x, y, corners| Cell {
+            x,
+            y,
+            w,
+            h,
+            corners,
+        };
+        [
+            cell(self.x, self.y, [bl, b, c, l]),
+            cell(self.x + w, self.y, [b, br, r, c]),
+            cell(self.x + w, self.y + h, [c, r, tr, t]),
+            cell(self.x, self.y + h, [l, c, t, tl]),
+        ]
+    }
+
+    /// returns the pieces of the level curve inside the cell, locating the
+    /// crossings on each edge by linear interpolation
+    ///
+    /// the saddle cases (opposite corners sharing a sign) are resolved with
+    /// the value at the center of the cell
+    fn marching_squares(&self, center: f64) -> Vec<(Point, Point)> {
+        let corner_points = [
+            Point::new(self.x, self.y),
+            Point::new(self.x + self.w, self.y),
+            Point::new(self.x + self.w, self.y + self.h),
+            Point::new(self.x, self.y + self.h),
+        ];
+
+        // edge k joins corner k and corner k + 1: bottom, right, top, left
+        let crossings: [Option<Point>; 4] = std::array::from_fn(|k| {
+            let (a, b) = (self.corners[k], self.corners[(k + 1) % 4]);
+            if (a > 0.0) == (b > 0.0) {
+                return None;
+            }
+            let t = a / (a - b);
+            let (p, q) = (corner_points[k], corner_points[(k + 1) % 4]);
+            Some(Point::new(p.x + t * (q.x - p.x), p.y + t * (q.y - p.y)))
+        });
+
+        let found: Vec<Point> = crossings.iter().flatten().copied().collect();
+        if found.len() == 2 {
+            return vec![(found[0], found[1])];
+        }
+        if found.len() != 4 {
+            return Vec::new();
+        }
+
+        // saddle: cut off the two corners on the other side from the center,
+        // corner k touches edges k - 1 and k
+        (0..4)
+            .filter(|&k| (self.corners[k] > 0.0) != (center > 0.0))
+            .filter_map(|k| Some((crossings[(k + 3) % 4]?, crossings[k]?)))
+            .collect()
+    }
+}
+
+/// picks the (major, minor) grid spacing for an axis spanning `extent` units
+/// over `pixels` pixels
+///
+/// major is the smallest 1/2/5 * 10^k that keeps lines `GRID_PIXEL_SPACING`
+/// apart, minor splits it into 5 (or 4 for a 2 * 10^k step)
+fn grid_spacing(extent: f64, pixels: f32) -> (f64, f64) {
+    let raw = extent * GRID_PIXEL_SPACING as f64 / pixels as f64;
+    let magnitude = 10f64.powf(raw.log10().floor());
+
+    let (major, subdivisions) = match raw / magnitude {
+        m if m <= 1.0 => (magnitude, 5.0),
+        m if m <= 2.0 => (2.0 * magnitude, 4.0),
+        m if m <= 5.0 => (5.0 * magnitude, 5.0),
+        _ => (10.0 * magnitude, 5.0),
+    };
+
+    (major, major / subdivisions)
+}
+
+/// multiples of `step` inside [min, max]
+fn grid_lines(min: f64, max: f64, step: f64) -> impl Iterator<Item = f64> {
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last).map(move |k| k as f64 * step)
+}
+
+/// formats a tick value with just enough decimals for the spacing, switching
+/// to scientific notation for very large or small spacings
+fn format_tick(value: f64, spacing: f64) -> String {
+    if !(1e-4..1e6).contains(&spacing) {
+        return format!("{:.1e}", value);
+    }
+    let decimals = (-spacing.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+/// color of the value w in a domain coloring: hue from arg(w), lightness
+/// 2/pi * atan(|w|), undefined values are gray
+fn domain_color(w: Complex64) -> Color {
+    if !w.is_finite() {
+        return GRAY;
+    }
+    let hue = w.arg().rem_euclid(std::f64::consts::TAU) / std::f64::consts::TAU;
+    let lightness = std::f64::consts::FRAC_2_PI * w.norm().atan();
+    color::hsl_to_rgb(hue as f32, 1.0, lightness as f32)
+}
+
+fn draw_polylines(segments: &[Vec<(f32, f32)>], color: Color) {
+    for segment in segments {
+        for pair in segment.windows(2) {
+            let (x1, y1) = pair[0];
+            let (x2, y2) = pair[1];
+
+            draw_line(x1, y1, x2, y2, 3.0, color);
+        }
+    }
+}
+
+fn draw_dashed_vertical_line(x: f32, color: Color) {
+    let (dash, gap) = (8.0, 6.0);
+
+    let mut y = 0.0;
+    while y < screen_height() {
+        draw_line(x, y, x, y + dash, 1.0, color);
+        y += dash + gap;
+    }
+}
+
+fn is_finite(p: (f32, f32)) -> bool {
+    p.0.is_finite() && p.1.is_finite()
+}
+
+/// distance from p to the segment between a and b, in screen space
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len_sq = dx * dx + dy * dy;
+
+    // projection of p onto the segment, clamped to its endpoints
+    let s = if len_sq > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let (ex, ey) = (a.0 + s * dx - p.0, a.1 + s * dy - p.1);
+    (ex * ex + ey * ey).sqrt()
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_grid_spacing() {
+        // (extent, pixels) -> (major, minor)
+        let cases = [
+            ((20.0, 1000.0), (2.0, 0.5)),
+            ((20.0, 800.0), (5.0, 1.0)),
+            ((1.0, 1000.0), (0.1, 0.02)),
+            ((3.0e6, 1000.0), (5.0e5, 1.0e5)),
+        ];
+
+        for ((extent, pixels), (major, minor)) in cases {
+            let (a, b) = grid_spacing(extent, pixels);
+            assert!((a - major).abs() < 1e-9 * major, "{extent}: {a} != {major}");
+            assert!((b - minor).abs() < 1e-9 * minor, "{extent}: {b} != {minor}");
+        }
+    }
+
+    #[test]
+    fn test_format_tick() {
+        assert_eq!(format_tick(3.0 * 0.1, 0.1), "0.3");
+        assert_eq!(format_tick(-4.0, 2.0), "-4");
+        assert_eq!(format_tick(0.25, 0.05), "0.25");
+        assert_eq!(format_tick(2.0e6, 1.0e6), "2.0e6");
+    }
+
+    #[test]
+    fn test_marching_squares() {
+        // f(x, y) = x + y - 1 crosses the bottom edge at (1, 0) and the left
+        // edge at (0, 1)
+        let g = |x: f64, y: f64| x + y - 1.0;
+        let cell = Cell {
+            x: 0.0,
+            y: 0.0,
+            w: 2.0,
+            h: 2.0,
+            corners: [g(0.0, 0.0), g(2.0, 0.0), g(2.0, 2.0), g(0.0, 2.0)],
+        };
+        let pieces = cell.marching_squares(g(1.0, 1.0));
+        assert_eq!(pieces, vec![(Point::new(1.0, 0.0), Point::new(0.0, 1.0))]);
+
+        // saddle f(x, y) = x*y on [-1, 1]^2 with a positive center is cut
+        // around the two negative corners
+        let cell = Cell {
+            x: -1.0,
+            y: -1.0,
+            w: 2.0,
+            h: 2.0,
+            corners: [1.0, -1.0, 1.0, -1.0],
+        };
+        let pieces = cell.marching_squares(0.5);
+        assert_eq!(pieces.len(), 2);
+        for (p, q) in pieces {
+            // each piece joins the midpoints of two adjacent edges
+            assert!((p.x - q.x).abs() == 1.0 && (p.y - q.y).abs() == 1.0);
+        }
+
+        let sub = cell.subdivide(&|x, y| x * y);
+        assert_eq!(sub[0].corners, [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(sub[2].corners, [0.0, 0.0, 1.0, 0.0]);
+    }
+}
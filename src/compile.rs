@@ -0,0 +1,404 @@
+use crate::dag::{Dag, Node, NodeId};
+use crate::func::{EvaluateOne, Function};
+use crate::polynomial::poly_eval;
+
+// =============================================================================
+
+/// register an instruction reads from, each instruction writes the register
+/// with its own index
+type Reg = usize;
+
+/// one step of a compiled program
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    Var(usize),
+    Const(f64),
+    Add(Reg, Reg),
+    Sub(Reg, Reg),
+    Mul(Reg, Reg),
+    Div(Reg, Reg),
+    Neg(Reg),
+    Sin(Reg),
+    Cos(Reg),
+    Tan(Reg),
+    Exp(Reg),
+    Log(Reg),
+    PowI(Reg, i32),
+    /// polynomial in the register x with the coefficients
+    /// `coeffs[start..start + len]` of the program, by Horner's method
+    Poly {
+        x: Reg,
+        start: usize,
+        len: usize,
+    },
+}
+
+/// number of inputs `eval_batch` pushes through each instruction at once
+const BATCH_CHUNK: usize = 256;
+
+/// a `Function` lowered to a flat list of register instructions, for
+/// evaluating the same expression many times
+///
+/// shared subexpressions are computed once (the program is built from the
+/// hash-consed `Dag`), constant subexpressions are folded at compile time
+/// and instructions that don't contribute to the result are dropped
+#[derive(Debug, Clone)]
+pub struct CompiledFunction {
+    instrs: Vec<Instr>,
+    coeffs: Vec<f64>,
+    /// kept for `singularities`, which needs the tree
+    source: Function,
+}
+
+impl Function {
+    pub fn compile(&self) -> CompiledFunction {
+        CompiledFunction::new(self)
+    }
+}
+
+impl CompiledFunction {
+    pub fn new(f: &Function) -> Self {
+        let mut dag = Dag::new();
+        let root = dag.add_function(f);
+        CompiledFunction::from_dag(&dag, root)
+    }
+
+    /// compiles the expression rooted at `root`, e.g. a derivative taken
+    /// with `Dag::pdv`, without expanding the graph into a tree first
+    pub fn from_dag(dag: &Dag, root: NodeId) -> Self {
+        let mut program = CompiledFunction {
+            instrs: Vec::new(),
+            coeffs: Vec::new(),
+            source: dag.to_function(root),
+        };
+
+        // dag ids are a topological order, so operands are emitted first
+        let mut regs = vec![0; root + 1];
+        for id in dag.reachable(root) {
+            regs[id] = program.lower(dag.node(id), &regs);
+        }
+
+        program.eliminate_dead_code(regs[root]);
+        program
+    }
+
+    /// evaluates at the point args = [x_0, x_1, ...]
+    pub fn eval(&self, args: &[f64]) -> f64 {
+        let mut regs = Vec::with_capacity(self.instrs.len());
+        for instr in &self.instrs {
+            let value = self.exec(instr, &regs, args);
+            regs.push(value);
+        }
+        regs.last().copied().unwrap_or(0.0)
+    }
+
+    /// evaluates at each x (as x_0, other variables are 0)
+    ///
+    /// the inputs go through the program in chunks, one instruction at a time
+    /// for the whole chunk, which keeps the dispatch out of the inner loops
+    pub fn eval_batch(&self, xs: &[f64]) -> Vec<f64> {
+        let n = self.instrs.len();
+        let mut regs = vec![0.0; n * BATCH_CHUNK];
+        let mut result = Vec::with_capacity(xs.len());
+
+        for chunk in xs.chunks(BATCH_CHUNK) {
+            let m = chunk.len();
+            for (r, instr) in self.instrs.iter().enumerate() {
+                // registers before r are inputs, r is the output
+                let (inputs, rest) = regs.split_at_mut(r * BATCH_CHUNK);
+                let out = &mut rest[..m];
+                let reg = |a: Reg| &inputs[a * BATCH_CHUNK..a * BATCH_CHUNK + m];
+
+                let unary = |out: &mut [f64], a: Reg, op: fn(f64) -> f64| {
+                    for (o, &x) in out.iter_mut().zip(reg(a)) {
+                        *o = op(x);
+                    }
+                };
+                let binary = |out: &mut [f64], a: Reg, b: Reg, op: fn(f64, f64) -> f64| {
+                    for ((o, &x), &y) in out.iter_mut().zip(reg(a)).zip(reg(b)) {
+                        *o = op(x, y);
+                    }
+                };
+
+                match *instr {
+                    Instr::Var(0) => out.copy_from_slice(chunk),
+                    Instr::Var(_) => out.fill(0.0),
+                    Instr::Const(c) => out.fill(c),
+                    Instr::Add(a, b) => binary(out, a, b, |x, y| x + y),
+                    Instr::Sub(a, b) => binary(out, a, b, |x, y| x - y),
+                    Instr::Mul(a, b) => binary(out, a, b, |x, y| x * y),
+                    Instr::Div(a, b) => binary(out, a, b, |x, y| x / y),
+                    Instr::Neg(a) => unary(out, a, |x| -x),
+                    Instr::Sin(a) => unary(out, a, f64::sin),
+                    Instr::Cos(a) => unary(out, a, f64::cos),
+                    Instr::Tan(a) => unary(out, a, f64::tan),
+                    Instr::Exp(a) => unary(out, a, f64::exp),
+                    Instr::Log(a) => unary(out, a, f64::ln),
+                    Instr::PowI(a, k) => {
+                        for (o, &x) in out.iter_mut().zip(reg(a)) {
+                            *o = x.powi(k);
+                        }
+                    }
+                    Instr::Poly { x, start, len } => {
+                        let coeffs = &self.coeffs[start..start + len];
+                        for (o, &x) in out.iter_mut().zip(reg(x)) {
+                            *o = poly_eval(coeffs, x);
+                        }
+                    }
+                }
+            }
+
+            match n {
+                0 => result.extend(std::iter::repeat_n(0.0, m)),
+                _ => result.extend_from_slice(&regs[(n - 1) * BATCH_CHUNK..][..m]),
+            }
+        }
+
+        result
+    }
+
+    fn exec(&self, instr: &Instr, regs: &[f64], args: &[f64]) -> f64 {
+        match *instr {
+            Instr::Var(i) => args.get(i).copied().unwrap_or(0.0),
+            Instr::Const(c) => c,
+            Instr::Add(a, b) => regs[a] + regs[b],
+            Instr::Sub(a, b) => regs[a] - regs[b],
+            Instr::Mul(a, b) => regs[a] * regs[b],
+            Instr::Div(a, b) => regs[a] / regs[b],
+            Instr::Neg(a) => -regs[a],
+            Instr::Sin(a) => regs[a].sin(),
+            Instr::Cos(a) => regs[a].cos(),
+            Instr::Tan(a) => regs[a].tan(),
+            Instr::Exp(a) => regs[a].exp(),
+            Instr::Log(a) => regs[a].ln(),
+            Instr::PowI(a, n) => regs[a].powi(n),
+            Instr::Poly { x, start, len } => poly_eval(&self.coeffs[start..start + len], regs[x]),
+        }
+    }
+
+    // lowering ----------------------------------------------------------------
+
+    /// emits the instructions for a dag node whose operands live in the
+    /// registers `regs[child id]`, returns the register of the result
+    fn lower(&mut self, node: &Node, regs: &[Reg]) -> Reg {
+        let r = |id: &usize| regs[*id];
+        match node {
+            Node::Var(i) => self.emit(Instr::Var(*i)),
+            Node::Const(c) => self.emit(Instr::Const(*c)),
+            Node::Add(a, b) => self.emit(Instr::Add(r(a), r(b))),
+            Node::Sub(a, b) => self.emit(Instr::Sub(r(a), r(b))),
+            Node::Mul(a, b) => self.emit(Instr::Mul(r(a), r(b))),
+            Node::Div(a, b) => self.emit(Instr::Div(r(a), r(b))),
+            Node::Neg(a) => self.emit(Instr::Neg(r(a))),
+            Node::Sin(a) => self.emit(Instr::Sin(r(a))),
+            Node::Cos(a) => self.emit(Instr::Cos(r(a))),
+            Node::Tan(a) => self.emit(Instr::Tan(r(a))),
+            Node::Exp(a) => self.emit(Instr::Exp(r(a))),
+            Node::Log(a) => self.emit(Instr::Log(r(a))),
+            Node::PowI(a, n) => self.emit(Instr::PowI(r(a), *n)),
+            Node::Sum(ids) => match ids.split_first() {
+                None => self.emit(Instr::Const(0.0)),
+                Some((first, rest)) => rest
+                    .iter()
+                    .fold(r(first), |acc, id| self.emit(Instr::Add(acc, r(id)))),
+            },
+            Node::Prod(ids) => match ids.split_first() {
+                None => self.emit(Instr::Const(1.0)),
+                Some((first, rest)) => rest
+                    .iter()
+                    .fold(r(first), |acc, id| self.emit(Instr::Mul(acc, r(id)))),
+            },
+            Node::Poly(coeffs) => {
+                let x = self.emit(Instr::Var(0));
+                let start = self.coeffs.len();
+                self.coeffs.extend_from_slice(coeffs);
+                self.emit(Instr::Poly {
+                    x,
+                    start,
+                    len: coeffs.len(),
+                })
+            }
+            // Horner's method with function coefficients
+            Node::PolyF(ids, i) => match ids.split_last() {
+                None => self.emit(Instr::Const(0.0)),
+                Some((last, rest)) => {
+                    let x = self.emit(Instr::Var(*i));
+                    rest.iter().rev().fold(r(last), |acc, id| {
+                        let scaled = self.emit(Instr::Mul(acc, x));
+                        self.emit(Instr::Add(scaled, r(id)))
+                    })
+                }
+            },
+        }
+    }
+
+    /// appends the instruction, or its value if all its operands are
+    /// constants, and returns its register
+    fn emit(&mut self, instr: Instr) -> Reg {
+        let instr = match self.fold(&instr) {
+            Some(c) => Instr::Const(c),
+            None => instr,
+        };
+        self.instrs.push(instr);
+        self.instrs.len() - 1
+    }
+
+    /// the value of an instruction whose operands are all constants, computed
+    /// by running it on just those constants
+    fn fold(&self, instr: &Instr) -> Option<f64> {
+        let ops = operands(instr);
+        if ops.is_empty() {
+            return None;
+        }
+        let values = ops
+            .iter()
+            .map(|&r| match self.instrs[r] {
+                Instr::Const(c) => Some(c),
+                _ => None,
+            })
+            .collect::<Option<Vec<f64>>>()?;
+
+        // operand k reads register k of `values`
+        let local = rename(*instr, |r| ops.iter().position(|&a| a == r).unwrap());
+        Some(self.exec(&local, &values, &[]))
+    }
+
+    /// removes the instructions the output doesn't depend on and makes the
+    /// output the last instruction
+    fn eliminate_dead_code(&mut self, output: Reg) {
+        let mut live = vec![false; output + 1];
+        live[output] = true;
+        for r in (0..=output).rev() {
+            if live[r] {
+                for a in operands(&self.instrs[r]) {
+                    live[a] = true;
+                }
+            }
+        }
+
+        let mut renamed = vec![0; output + 1];
+        let mut instrs = Vec::new();
+        for r in (0..=output).filter(|&r| live[r]) {
+            renamed[r] = instrs.len();
+            instrs.push(rename(self.instrs[r], |a| renamed[a]));
+        }
+
+        self.instrs = instrs;
+    }
+}
+
+/// the instruction with each register operand a replaced by `f(a)`
+fn rename<F: Fn(Reg) -> Reg>(instr: Instr, f: F) -> Instr {
+    match instr {
+        Instr::Add(a, b) => Instr::Add(f(a), f(b)),
+        Instr::Sub(a, b) => Instr::Sub(f(a), f(b)),
+        Instr::Mul(a, b) => Instr::Mul(f(a), f(b)),
+        Instr::Div(a, b) => Instr::Div(f(a), f(b)),
+        Instr::Neg(a) => Instr::Neg(f(a)),
+        Instr::Sin(a) => Instr::Sin(f(a)),
+        Instr::Cos(a) => Instr::Cos(f(a)),
+        Instr::Tan(a) => Instr::Tan(f(a)),
+        Instr::Exp(a) => Instr::Exp(f(a)),
+        Instr::Log(a) => Instr::Log(f(a)),
+        Instr::PowI(a, n) => Instr::PowI(f(a), n),
+        Instr::Poly { x, start, len } => Instr::Poly {
+            x: f(x),
+            start,
+            len,
+        },
+        instr @ (Instr::Var(_) | Instr::Const(_)) => instr,
+    }
+}
+
+fn operands(instr: &Instr) -> Vec<Reg> {
+    match *instr {
+        Instr::Var(_) | Instr::Const(_) => vec![],
+        Instr::Add(a, b) | Instr::Sub(a, b) | Instr::Mul(a, b) | Instr::Div(a, b) => vec![a, b],
+        Instr::Neg(a)
+        | Instr::Sin(a)
+        | Instr::Cos(a)
+        | Instr::Tan(a)
+        | Instr::Exp(a)
+        | Instr::Log(a)
+        | Instr::PowI(a, _)
+        | Instr::Poly { x: a, .. } => vec![a],
+    }
+}
+
+impl EvaluateOne for CompiledFunction {
+    fn eval_one(&self, x: f64) -> f64 {
+        self.eval(&[x])
+    }
+
+    fn eval_each(&self, xs: &[f64]) -> Vec<f64> {
+        self.eval_batch(xs)
+    }
+
+    fn singularities(&self, interval: (f64, f64)) -> Vec<f64> {
+        self.source.singularities(interval)
+    }
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::func::*;
+
+    fn assert_agrees(f: &Function) {
+        let compiled = f.compile();
+        let xs: Vec<f64> = (0..1000).map(|i| -3.0 + 0.00631 * i as f64).collect();
+        let batch = compiled.eval_batch(&xs);
+
+        for (&x, &b) in xs.iter().zip(&batch) {
+            let expected: f64 = f.eval([x, 0.5]);
+            let one = compiled.eval(&[x, 0.5]);
+            assert!(
+                expected.to_bits() == one.to_bits() || (expected - one).abs() < 1e-12,
+                "{f} at {x}: {one} != {expected}"
+            );
+            if f.num_vars() <= 1 {
+                let expected: f64 = f.eval([x]);
+                assert!(b == expected || (b.is_nan() && expected.is_nan()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compiled_eval() {
+        let functions = [
+            fn_add(fn_sin(X), fn_mul(fn_const(2.0), Y)),
+            fn_div(fn_exp(fn_neg(X)), fn_sub(fn_tan(X), fn_log(X))),
+            fn_sum(vec![fn_powi(X, -3), fn_prod(vec![X, fn_cos(X), X])]),
+            fn_sum(vec![]),
+            fn_poly(vec![1.0, -2.0, 0.5, 0.25]),
+            Function::PolyF(vec![fn_sin(Y), X, fn_const(3.0)], 1),
+            Function::PolyF(vec![fn_sin(X), Y, fn_const(3.0)], 0),
+            fn_const(4.0),
+        ];
+        for f in &functions {
+            assert_agrees(f);
+        }
+    }
+
+    #[test]
+    fn test_folding_and_sharing() {
+        // the constant subexpression exp(2) * sin(1) folds into one constant
+        let c = Function::Mul(
+            Box::new(fn_exp(fn_const(2.0))),
+            Box::new(fn_sin(fn_const(1.0))),
+        );
+        let f = fn_mul(c, X);
+        let compiled = f.compile();
+        assert_eq!(compiled.instrs.len(), 3);
+        assert!(compiled
+            .instrs
+            .contains(&Instr::Const(2f64.exp() * 1f64.sin())));
+
+        // sin(x) * sin(x) + sin(x) computes sin(x) once
+        let s = fn_sin(X);
+        let g = fn_add(fn_mul(s.clone(), s.clone()), s);
+        assert_eq!(g.compile().instrs.len(), 4);
+    }
+}
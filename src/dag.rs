@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher, RandomState};
+
+use hashbrown::HashTable;
+
+// -----------------------------------------------------------------------------
+
+use crate::func::Function;
+use crate::polynomial::{poly_derivative, poly_eval};
+use crate::scalar::Scalar;
+
+// =============================================================================
+
+/// index of a node in a `Dag`
+pub type NodeId = usize;
+
+/// one operation of a `Dag`, the variants mirror `Function` with subtrees
+/// replaced by the ids of their nodes
+#[derive(Debug, Clone)]
+pub enum Node {
+    Var(usize),
+    Const(f64),
+    Add(NodeId, NodeId),
+    Sub(NodeId, NodeId),
+    Neg(NodeId),
+    Mul(NodeId, NodeId),
+    Div(NodeId, NodeId),
+    Sin(NodeId),
+    Cos(NodeId),
+    Tan(NodeId),
+    Exp(NodeId),
+    Log(NodeId),
+    Sum(Vec<NodeId>),
+    Prod(Vec<NodeId>),
+    PowI(NodeId, i32),
+    Poly(Vec<f64>),
+    PolyF(Vec<NodeId>, usize),
+}
+
+/// expressions stored as a directed acyclic graph in an arena
+///
+/// nodes are hash-consed: inserting a node equal to an existing one returns
+/// the existing id, so identical subexpressions are stored (and evaluated)
+/// once no matter how often they occur. children are always inserted before
+/// their parents, so ids are a topological order
+#[derive(Debug, Clone, Default)]
+pub struct Dag {
+    nodes: Vec<Node>,
+    /// the ids of the nodes by the hash of the node, so each node is only
+    /// stored in `nodes`
+    ids: HashTable<NodeId>,
+    hasher: RandomState,
+}
+
+impl Dag {
+    pub fn new() -> Self {
+        Dag::default()
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    /// returns the id of the node, adding it only if no equal node exists
+    pub fn insert(&mut self, node: Node) -> NodeId {
+        let hash = self.hasher.hash_one(&node);
+        let nodes = &self.nodes;
+        if let Some(&id) = self.ids.find(hash, |&id| nodes[id] == node) {
+            return id;
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        let (nodes, hasher) = (&self.nodes, &self.hasher);
+        self.ids
+            .insert_unique(hash, id, |&id| hasher.hash_one(&nodes[id]));
+        id
+    }
+
+    // conversion --------------------------------------------------------------
+
+    /// adds f to the graph and returns the id of its root
+    pub fn add_function(&mut self, f: &Function) -> NodeId {
+        let node = match f {
+            Function::Var(i) => Node::Var(*i),
+            Function::Const(c) => Node::Const(*c),
+            Function::Add(f1, f2) => Node::Add(self.add_function(f1), self.add_function(f2)),
+            Function::Sub(f1, f2) => Node::Sub(self.add_function(f1), self.add_function(f2)),
+            Function::Neg(g) => Node::Neg(self.add_function(g)),
+            Function::Mul(f1, f2) => Node::Mul(self.add_function(f1), self.add_function(f2)),
+            Function::Div(f1, f2) => Node::Div(self.add_function(f1), self.add_function(f2)),
+            Function::Sin(g) => Node::Sin(self.add_function(g)),
+            Function::Cos(g) => Node::Cos(self.add_function(g)),
+            Function::Tan(g) => Node::Tan(self.add_function(g)),
+            Function::Exp(g) => Node::Exp(self.add_function(g)),
+            Function::Log(g) => Node::Log(self.add_function(g)),
+            Function::Sum(fs) => Node::Sum(fs.iter().map(|g| self.add_function(g)).collect()),
+            Function::Prod(fs) => Node::Prod(fs.iter().map(|g| self.add_function(g)).collect()),
+            Function::PowI(g, n) => Node::PowI(self.add_function(g), *n),
+            Function::Poly(coeffs) => Node::Poly(coeffs.clone()),
+            Function::PolyF(fs, i) => {
+                Node::PolyF(fs.iter().map(|g| self.add_function(g)).collect(), *i)
+            }
+        };
+        self.insert(node)
+    }
+
+    /// expands the node back into a tree, the exact inverse of `add_function`
+    pub fn to_function(&self, id: NodeId) -> Function {
+        let f = |id: &NodeId| Box::new(self.to_function(*id));
+        let fs = |ids: &[NodeId]| ids.iter().map(|&id| self.to_function(id)).collect();
+        match &self.nodes[id] {
+            Node::Var(i) => Function::Var(*i),
+            Node::Const(c) => Function::Const(*c),
+            Node::Add(a, b) => Function::Add(f(a), f(b)),
+            Node::Sub(a, b) => Function::Sub(f(a), f(b)),
+            Node::Neg(a) => Function::Neg(f(a)),
+            Node::Mul(a, b) => Function::Mul(f(a), f(b)),
+            Node::Div(a, b) => Function::Div(f(a), f(b)),
+            Node::Sin(a) => Function::Sin(f(a)),
+            Node::Cos(a) => Function::Cos(f(a)),
+            Node::Tan(a) => Function::Tan(f(a)),
+            Node::Exp(a) => Function::Exp(f(a)),
+            Node::Log(a) => Function::Log(f(a)),
+            Node::Sum(ids) => Function::Sum(fs(ids)),
+            Node::Prod(ids) => Function::Prod(fs(ids)),
+            Node::PowI(a, n) => Function::PowI(f(a), *n),
+            Node::Poly(coeffs) => Function::Poly(coeffs.clone()),
+            Node::PolyF(ids, i) => Function::PolyF(fs(ids), *i),
+        }
+    }
+
+    // evaluation --------------------------------------------------------------
+
+    /// evaluates the expression rooted at id, computing every node reachable
+    /// from it exactly once
+    pub fn eval<S: Scalar>(&self, root: NodeId, args: &[S]) -> S {
+        let arg = |i: usize| args.get(i).cloned().unwrap_or_else(S::zero);
+
+        let mut values: Vec<Option<S>> = vec![None; root + 1];
+        for id in self.reachable(root) {
+            // children have smaller ids, so they are already computed
+            let v = |c: &NodeId| values[*c].clone().unwrap();
+            let value = match &self.nodes[id] {
+                Node::Var(i) => arg(*i),
+                Node::Const(c) => S::from_f64(*c),
+                Node::Add(a, b) => v(a) + v(b),
+                Node::Sub(a, b) => v(a) - v(b),
+                Node::Neg(a) => -v(a),
+                Node::Mul(a, b) => v(a) * v(b),
+                Node::Div(a, b) => v(a) / v(b),
+                Node::Sin(a) => v(a).sin(),
+                Node::Cos(a) => v(a).cos(),
+                Node::Tan(a) => v(a).tan(),
+                Node::Exp(a) => v(a).exp(),
+                Node::Log(a) => v(a).ln(),
+                Node::Sum(ids) => ids.iter().fold(S::zero(), |acc, c| acc + v(c)),
+                Node::Prod(ids) => ids.iter().fold(S::one(), |acc, c| acc * v(c)),
+                Node::PowI(a, n) => v(a).powi(*n),
+                Node::Poly(coeffs) => poly_eval(coeffs, arg(0)),
+                Node::PolyF(ids, i) => {
+                    let x = arg(*i);
+                    ids.iter().enumerate().fold(S::zero(), |acc, (k, c)| {
+                        acc + v(c) * x.clone().powi(k as i32)
+                    })
+                }
+            };
+            values[id] = Some(value);
+        }
+
+        values[root].take().unwrap()
+    }
+
+    /// ids of the nodes the root depends on (itself included), ascending
+    pub fn reachable(&self, root: NodeId) -> Vec<NodeId> {
+        let mut seen = vec![false; root + 1];
+        let mut stack = vec![root];
+        seen[root] = true;
+        while let Some(id) = stack.pop() {
+            for c in self.nodes[id].children() {
+                if !seen[c] {
+                    seen[c] = true;
+                    stack.push(c);
+                }
+            }
+        }
+        (0..=root).filter(|&id| seen[id]).collect()
+    }
+
+    // differentiation ---------------------------------------------------------
+
+    /// symbolically computes the partial derivative of the expression rooted
+    /// at id with respect to x_i, with the same rules and folding as `fn_pdv`
+    ///
+    /// derivatives are memoized per node and results are hash-consed, so
+    /// repeated differentiation grows the graph polynomially instead of
+    /// exponentially
+    pub fn pdv(&mut self, id: NodeId, i: usize) -> NodeId {
+        self.pdv_memo(id, i, &mut HashMap::new())
+    }
+
+    fn pdv_memo(&mut self, id: NodeId, i: usize, memo: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if let Some(&d) = memo.get(&id) {
+            return d;
+        }
+
+        let node = self.nodes[id].clone();
+        let mut d = |dag: &mut Dag, c: NodeId| dag.pdv_memo(c, i, memo);
+
+        let result = match node {
+            Node::Var(j) => self.constant(if i == j { 1.0 } else { 0.0 }),
+            Node::Const(_) => self.constant(0.0),
+            Node::Add(a, b) => {
+                let (da, db) = (d(self, a), d(self, b));
+                self.add(da, db)
+            }
+            Node::Sub(a, b) => {
+                let (da, db) = (d(self, a), d(self, b));
+                self.sub(da, db)
+            }
+            Node::Neg(a) => {
+                let da = d(self, a);
+                self.neg(da)
+            }
+            Node::Mul(a, b) => {
+                let (da, db) = (d(self, a), d(self, b));
+                let (l, r) = (self.mul(da, b), self.mul(a, db));
+                self.add(l, r)
+            }
+            Node::Div(a, b) => {
+                let (da, db) = (d(self, a), d(self, b));
+                let (l, r) = (self.mul(da, b), self.mul(a, db));
+                let num = self.sub(l, r);
+                let den = self.mul(b, b);
+                self.div(num, den)
+            }
+            Node::Sin(a) => {
+                let da = d(self, a);
+                let cos = self.insert(Node::Cos(a));
+                self.mul(cos, da)
+            }
+            Node::Cos(a) => {
+                let da = d(self, a);
+                let sin = self.insert(Node::Sin(a));
+                let minus_sin = self.neg(sin);
+                self.mul(minus_sin, da)
+            }
+            Node::Tan(a) => {
+                let da = d(self, a);
+                let cos = self.insert(Node::Cos(a));
+                let cos2 = self.insert(Node::PowI(cos, 2));
+                let one = self.constant(1.0);
+                let sec2 = self.div(one, cos2);
+                self.mul(sec2, da)
+            }
+            Node::Exp(a) => {
+                let da = d(self, a);
+                self.mul(id, da)
+            }
+            Node::Log(a) => {
+                let da = d(self, a);
+                let one = self.constant(1.0);
+                let inv = self.div(one, a);
+                self.mul(inv, da)
+            }
+            Node::Sum(ids) => {
+                let terms = ids.iter().map(|&c| d(self, c)).collect();
+                self.insert(Node::Sum(terms))
+            }
+            Node::Prod(ids) => {
+                let mut summands = Vec::new();
+                for (j, &c) in ids.iter().enumerate() {
+                    let mut others = ids.clone();
+                    others.remove(j);
+                    let dc = d(self, c);
+                    let rest = self.insert(Node::Prod(others));
+                    summands.push(self.mul(dc, rest));
+                }
+                self.insert(Node::Sum(summands))
+            }
+            Node::PowI(a, n) => {
+                let da = d(self, a);
+                let factor = self.constant(n as f64);
+                let power = self.powi(a, n - 1);
+                let inner = self.mul(power, da);
+                self.mul(factor, inner)
+            }
+            // polynomials are in x_0, so constant in every other variable
+            Node::Poly(_) if i != 0 => self.constant(0.0),
+            Node::Poly(coeffs) => {
+                let derivative = poly_derivative(&coeffs);
+                match derivative.len() {
+                    0 => self.constant(0.0),
+                    1 => self.constant(derivative[0]),
+                    _ => self.insert(Node::Poly(derivative)),
+                }
+            }
+            Node::PolyF(ids, j) => {
+                // d(f_k x^k) = df_k x^k + f_k d(x^k)
+                let x = self.insert(Node::Var(j));
+                let dx = self.constant(if i == j { 1.0 } else { 0.0 });
+                let mut terms = Vec::new();
+                for (k, &c) in ids.iter().enumerate() {
+                    let k = k as i32;
+                    let dc = d(self, c);
+                    let xk = self.powi(x, k);
+                    let left = self.mul(dc, xk);
+
+                    let factor = self.constant(k as f64);
+                    let xk1 = self.powi(x, k - 1);
+                    let dxk = self.mul(xk1, dx);
+                    let dxk = self.mul(factor, dxk);
+                    let right = self.mul(c, dxk);
+
+                    terms.push(self.add(left, right));
+                }
+                self.insert(Node::Sum(terms))
+            }
+        };
+
+        memo.insert(id, result);
+        result
+    }
+
+    // constructors with the folding of the fn_* functions ---------------------
+
+    fn constant(&mut self, c: f64) -> NodeId {
+        self.insert(Node::Const(c))
+    }
+
+    fn as_const(&self, id: NodeId) -> Option<f64> {
+        match self.nodes[id] {
+            Node::Const(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    fn add(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        match (self.as_const(a), self.as_const(b)) {
+            (Some(x), Some(y)) => self.constant(x + y),
+            (Some(0.0), _) => b,
+            (_, Some(0.0)) => a,
+            _ => self.insert(Node::Add(a, b)),
+        }
+    }
+
+    fn sub(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        match (self.as_const(a), self.as_const(b)) {
+            (Some(x), Some(y)) => self.constant(x - y),
+            (_, Some(0.0)) => a,
+            (Some(0.0), _) => self.neg(b),
+            _ => self.insert(Node::Sub(a, b)),
+        }
+    }
+
+    fn neg(&mut self, a: NodeId) -> NodeId {
+        match self.nodes[a] {
+            Node::Const(c) => self.constant(-c),
+            Node::Neg(b) => b,
+            _ => self.insert(Node::Neg(a)),
+        }
+    }
+
+    fn mul(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        match (self.as_const(a), self.as_const(b)) {
+            (Some(x), Some(y)) => self.constant(x * y),
+            (Some(0.0), _) | (_, Some(0.0)) => self.constant(0.0),
+            (Some(1.0), _) => b,
+            (_, Some(1.0)) => a,
+            _ => self.insert(Node::Mul(a, b)),
+        }
+    }
+
+    fn div(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        match (self.as_const(a), self.as_const(b)) {
+            (Some(x), Some(y)) => self.constant(x / y),
+            (_, Some(1.0)) => a,
+            _ => self.insert(Node::Div(a, b)),
+        }
+    }
+
+    fn powi(&mut self, a: NodeId, n: i32) -> NodeId {
+        match (self.as_const(a), n) {
+            (Some(c), n) => self.constant(c.powi(n)),
+            (_, 0) => self.constant(1.0),
+            (_, 1) => a,
+            (_, n) => self.insert(Node::PowI(a, n)),
+        }
+    }
+}
+
+// nodes -----------------------------------------------------------------------
+
+impl Node {
+    pub fn children(&self) -> Vec<NodeId> {
+        match self {
+            Node::Var(_) | Node::Const(_) | Node::Poly(_) => Vec::new(),
+            Node::Add(a, b) | Node::Sub(a, b) | Node::Mul(a, b) | Node::Div(a, b) => vec![*a, *b],
+            Node::Neg(a)
+            | Node::Sin(a)
+            | Node::Cos(a)
+            | Node::Tan(a)
+            | Node::Exp(a)
+            | Node::Log(a)
+            | Node::PowI(a, _) => vec![*a],
+            Node::Sum(ids) | Node::Prod(ids) | Node::PolyF(ids, _) => ids.clone(),
+        }
+    }
+
+    /// the node with constants as bit patterns, which `Eq` and `Hash`
+    /// compare (like `Function`, NaN == NaN and 0 != -0). borrows the child
+    /// lists, so hash-cons lookups don't allocate
+    fn key(&self) -> Key<'_> {
+        match self {
+            Node::Var(i) => Key::Leaf(0, *i as u64),
+            Node::Const(c) => Key::Leaf(1, c.to_bits()),
+            Node::Add(a, b) => Key::Binary(2, *a, *b),
+            Node::Sub(a, b) => Key::Binary(3, *a, *b),
+            Node::Neg(a) => Key::Unary(4, *a),
+            Node::Mul(a, b) => Key::Binary(5, *a, *b),
+            Node::Div(a, b) => Key::Binary(6, *a, *b),
+            Node::Sin(a) => Key::Unary(7, *a),
+            Node::Cos(a) => Key::Unary(8, *a),
+            Node::Tan(a) => Key::Unary(9, *a),
+            Node::Exp(a) => Key::Unary(10, *a),
+            Node::Log(a) => Key::Unary(11, *a),
+            Node::Sum(cs) => Key::Nary(12, cs),
+            Node::Prod(cs) => Key::Nary(13, cs),
+            Node::PowI(a, n) => Key::PowI(*a, *n),
+            Node::Poly(coeffs) => Key::Poly(FloatBits(coeffs)),
+            Node::PolyF(cs, i) => Key::PolyF(cs, *i),
+        }
+    }
+}
+
+/// what `Node` compares and hashes by, the `u8` tells apart the variants of
+/// the same shape
+#[derive(PartialEq, Eq, Hash)]
+enum Key<'a> {
+    Leaf(u8, u64),
+    Unary(u8, NodeId),
+    Binary(u8, NodeId, NodeId),
+    Nary(u8, &'a [NodeId]),
+    PowI(NodeId, i32),
+    Poly(FloatBits<'a>),
+    PolyF(&'a [NodeId], usize),
+}
+
+/// floats compared and hashed by their bit patterns
+struct FloatBits<'a>(&'a [f64]);
+
+impl PartialEq for FloatBits<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0)
+                .all(|(a, b)| a.to_bits() == b.to_bits())
+    }
+}
+
+impl Eq for FloatBits<'_> {}
+
+impl Hash for FloatBits<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.0.len());
+        for c in self.0 {
+            c.to_bits().hash(state);
+        }
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Node {}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::func::*;
+
+    /// number of nodes the expression rooted at id would have as a tree
+    /// (saturating), compare with the number of nodes to see how much is
+    /// shared
+    fn tree_size(dag: &Dag, root: NodeId) -> u64 {
+        let mut sizes = vec![0u64; root + 1];
+        for id in dag.reachable(root) {
+            sizes[id] = dag.nodes[id]
+                .children()
+                .iter()
+                .fold(1u64, |acc, &c| acc.saturating_add(sizes[c]));
+        }
+        sizes[root]
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let functions = [
+            fn_add(fn_sin(X), fn_mul(fn_const(2.0), Y)),
+            fn_div(fn_exp(fn_neg(X)), fn_sub(fn_tan(Y), fn_log(Z))),
+            fn_sum(vec![fn_powi(X, -3), fn_prod(vec![X, fn_cos(X), X])]),
+            fn_poly(vec![1.0, -2.0, 0.5]),
+            Function::PolyF(vec![fn_sin(Y), X, fn_const(3.0)], 1),
+            fn_const(f64::NAN),
+        ];
+
+        let mut dag = Dag::new();
+        for f in &functions {
+            let id = dag.add_function(f);
+            assert_eq!(dag.to_function(id), *f);
+
+            let args = [0.3, 1.2, 2.5];
+            let (a, b): (f64, f64) = (dag.eval(id, &args), f.eval(args));
+            assert!(a == b || (a.is_nan() && b.is_nan()));
+        }
+    }
+
+    #[test]
+    fn test_sharing() {
+        // sin(x) + sin(x) * sin(x) stores x and sin(x) once
+        let s = fn_sin(X);
+        let f = fn_add(s.clone(), fn_mul(s.clone(), s));
+
+        let mut dag = Dag::new();
+        let id = dag.add_function(&f);
+        assert_eq!(dag.nodes.len(), 4);
+        assert_eq!(tree_size(&dag, id), 8);
+
+        // adding it again adds nothing
+        assert_eq!(dag.add_function(&f), id);
+        assert_eq!(dag.nodes.len(), 4);
+        assert_eq!(dag.ids.len(), 4);
+
+        // constants are keyed by their bits
+        let p = dag.insert(Node::Poly(vec![1.0, f64::NAN]));
+        assert_eq!(dag.insert(Node::Poly(vec![1.0, f64::NAN])), p);
+        assert_ne!(dag.insert(Node::Const(0.0)), dag.insert(Node::Const(-0.0)));
+        assert_ne!(
+            dag.insert(Node::Sum(vec![0, 1])),
+            dag.insert(Node::Prod(vec![0, 1]))
+        );
+    }
+
+    #[test]
+    fn test_higher_derivatives() {
+        // f = x * sin(x) / (1 + x^2)
+        let f = fn_div(fn_mul(X, fn_sin(X)), fn_add(fn_const(1.0), fn_powi(X, 2)));
+
+        let mut dag = Dag::new();
+        let mut id = dag.add_function(&f);
+        let mut tree = f.clone();
+        for _ in 0..4 {
+            id = dag.pdv(id, 0);
+            tree = fn_pdv(&tree, 0);
+        }
+
+        for x in [-1.5, 0.2, 0.7, 3.0] {
+            let (a, b): (f64, f64) = (dag.eval(id, &[x]), tree.eval([x]));
+            assert!((a - b).abs() < 1e-9 * (1.0 + b.abs()));
+        }
+
+        // polynomials keep their own variant
+        let p = dag.add_function(&Function::Poly(vec![1.0, 2.0, 3.0]));
+        let dp = dag.pdv(p, 0);
+        assert_eq!(dag.to_function(dp), Function::Poly(vec![2.0, 6.0]));
+        let ddp = dag.pdv(dp, 0);
+        assert_eq!(dag.to_function(ddp), fn_const(6.0));
+
+        // the tree is far bigger than the graph it came from
+        assert!(tree_size(&dag, id) > 10 * dag.nodes.len() as u64);
+    }
+}
@@ -0,0 +1,159 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// -----------------------------------------------------------------------------
+
+use crate::scalar::Scalar;
+
+// =============================================================================
+
+/// dual number for forward-mode automatic differentiation
+///
+/// carries a value together with its gradient with respect to every input
+/// variable, so one evaluation gives the full gradient. constants have an
+/// empty gradient, which is treated as all zeros
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub grad: Vec<f64>,
+}
+
+impl Dual {
+    pub fn constant(value: f64) -> Self {
+        Dual {
+            value,
+            grad: Vec::new(),
+        }
+    }
+
+    /// the i-th of n input variables, with value x and gradient e_i
+    pub fn variable(x: f64, i: usize, n: usize) -> Self {
+        let mut grad = vec![0.0; n];
+        grad[i] = 1.0;
+        Dual { value: x, grad }
+    }
+
+    /// applies a function with derivative `df` at self.value (chain rule)
+    fn chain(self, value: f64, df: f64) -> Self {
+        Dual {
+            value,
+            grad: self.grad.into_iter().map(|g| df * g).collect(),
+        }
+    }
+}
+
+/// computes a * ga + b * gb, padding the shorter gradient with zeros
+fn combine(ga: &[f64], a: f64, gb: &[f64], b: f64) -> Vec<f64> {
+    (0..ga.len().max(gb.len()))
+        .map(|i| {
+            let x = ga.get(i).copied().unwrap_or(0.0);
+            let y = gb.get(i).copied().unwrap_or(0.0);
+            a * x + b * y
+        })
+        .collect()
+}
+
+// arithmetic ------------------------------------------------------------------
+
+impl Add for Dual {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Dual {
+            value: self.value + other.value,
+            grad: combine(&self.grad, 1.0, &other.grad, 1.0),
+        }
+    }
+}
+
+impl Sub for Dual {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Dual {
+            value: self.value - other.value,
+            grad: combine(&self.grad, 1.0, &other.grad, -1.0),
+        }
+    }
+}
+
+impl Neg for Dual {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        let value = -self.value;
+        self.chain(value, -1.0)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Self;
+
+    /// (fg)' = f'g + fg'
+    fn mul(self, other: Self) -> Self {
+        Dual {
+            value: self.value * other.value,
+            grad: combine(&self.grad, other.value, &other.grad, self.value),
+        }
+    }
+}
+
+impl Div for Dual {
+    type Output = Self;
+
+    /// (f/g)' = f'/g - fg'/g^2
+    fn div(self, other: Self) -> Self {
+        let value = self.value / other.value;
+        Dual {
+            value,
+            grad: combine(
+                &self.grad,
+                1.0 / other.value,
+                &other.grad,
+                -value / other.value,
+            ),
+        }
+    }
+}
+
+// elementary functions --------------------------------------------------------
+
+impl Scalar for Dual {
+    fn from_f64(c: f64) -> Self {
+        Dual::constant(c)
+    }
+
+    fn sin(self) -> Self {
+        let x = self.value;
+        self.chain(x.sin(), x.cos())
+    }
+
+    fn cos(self) -> Self {
+        let x = self.value;
+        self.chain(x.cos(), -x.sin())
+    }
+
+    fn tan(self) -> Self {
+        let t = self.value.tan();
+        self.chain(t, 1.0 + t * t)
+    }
+
+    fn exp(self) -> Self {
+        let e = self.value.exp();
+        self.chain(e, e)
+    }
+
+    fn ln(self) -> Self {
+        let x = self.value;
+        self.chain(x.ln(), 1.0 / x)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        let x = self.value;
+        let df = if n == 0 {
+            0.0
+        } else {
+            n as f64 * x.powi(n - 1)
+        };
+        self.chain(x.powi(n), df)
+    }
+}
@@ -1,11 +1,40 @@
 // create modules --------------------------------------------------------------
 
-use crate::polynomial::{poly_eval, poly_mul};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use ndarray::{Array1, ArrayView2};
+use num_complex::Complex64;
+
+use crate::dual::Dual;
+use crate::polynomial::{poly_derivative, poly_eval, poly_mul, Polynomial};
+use crate::rational::Rational;
+use crate::scalar::Scalar;
 
 // Evaluation ==================================================================
 
 pub trait EvaluateOne {
     fn eval_one(&self, x: f64) -> f64;
+
+    /// points of the interval where the function is known to blow up (poles)
+    ///
+    /// the plotter breaks curves there, by default none are known
+    fn singularities(&self, _interval: (f64, f64)) -> Vec<f64> {
+        Vec::new()
+    }
+
+    /// evaluates at each x, for implementations with a faster way than one
+    /// `eval_one` call per point
+    fn eval_each(&self, xs: &[f64]) -> Vec<f64> {
+        xs.iter().map(|&x| self.eval_one(x)).collect()
+    }
+}
+
+/// evaluation at many points at once
+pub trait EvaluateMany {
+    /// evaluates at each row of `points`, whose columns are the variables
+    /// x_0, x_1, ... (missing columns read as 0)
+    fn eval_batch(&self, points: &ArrayView2<f64>) -> Array1<f64>;
 }
 
 // Variable Index ==============================================================
@@ -19,6 +48,7 @@ type VarIdx = usize;
 // Function Expression =========================================================
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub enum Function {
     Var(VarIdx), // picks out the i-th variable of the input x = (x_1, x_2, ..., x_n)
     Const(f64),
@@ -42,12 +72,13 @@ pub enum Function {
 // evaluation ------------------------------------------------------------------
 
 impl Function {
-    pub fn eval<T: AsRef<[f64]>>(&self, args: T) -> f64 {
+    /// evaluates f at the point args = [x_0, x_1, ...] over any `Scalar` type
+    pub fn eval<S: Scalar, T: AsRef<[S]>>(&self, args: T) -> S {
         let args = args.as_ref();
         match self {
             // if i is out of bounds, return 0, should maybe return an Option::None
             Function::Var(i) => get_arg(args, *i),
-            Function::Const(c) => *c,
+            Function::Const(c) => S::from_f64(*c),
             Function::Add(f, g) => f.eval(args) + g.eval(args),
             Function::Sub(f, g) => f.eval(args) - g.eval(args),
             Function::Neg(f) => -f.eval(args),
@@ -58,20 +89,84 @@ impl Function {
             Function::Tan(f) => f.eval(args).tan(),
             Function::Exp(f) => f.eval(args).exp(),
             Function::Log(f) => f.eval(args).ln(),
-            Function::Sum(fs) => fs.iter().map(|f| f.eval(args)).sum(),
-            Function::Prod(fs) => fs.iter().map(|f| f.eval(args)).product(),
+            Function::Sum(fs) => fs.iter().fold(S::zero(), |acc, f| acc + f.eval(args)),
+            Function::Prod(fs) => fs.iter().fold(S::one(), |acc, f| acc * f.eval(args)),
             Function::PowI(f, n) => f.eval(args).powi(*n),
             Function::Poly(coeffs) => poly_eval(coeffs, get_arg(args, 0)),
             Function::PolyF(fs, i) => {
-                let x = get_arg(args, *i);
+                let x: S = get_arg(args, *i);
+                fs.iter().enumerate().fold(S::zero(), |acc, (i, f)| {
+                    acc + f.eval(args) * x.clone().powi(i as i32)
+                })
+            }
+        }
+    }
+
+    /// evaluates f over the complex numbers
+    ///
+    /// `Log` is the principal branch, with its cut along the negative real axis
+    pub fn eval_complex<T: AsRef<[Complex64]>>(&self, args: T) -> Complex64 {
+        self.eval(args)
+    }
+
+    /// evaluates f and its gradient (df/dx_0, ..., df/dx_n) at args in a single
+    /// pass, using forward-mode automatic differentiation
+    pub fn eval_with_gradient<T: AsRef<[f64]>>(&self, args: T) -> (f64, Vec<f64>) {
+        let args = args.as_ref();
+        let n = args.len();
+
+        let duals: Vec<Dual> = args
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| Dual::variable(x, i, n))
+            .collect();
+
+        let Dual { value, mut grad } = self.eval(duals);
+        grad.resize(n, 0.0);
+        (value, grad)
+    }
+
+    /// evaluates f at each row of `points` (columns are the variables), node
+    /// by node: every subexpression is computed for all points at once
+    pub fn eval_batch(&self, points: &ArrayView2<f64>) -> Array1<f64> {
+        let n = points.nrows();
+        let column = |i: usize| {
+            if i < points.ncols() {
+                points.column(i).to_owned()
+            } else {
+                Array1::zeros(n)
+            }
+        };
+        let eval = |f: &Function| f.eval_batch(points);
+
+        match self {
+            Function::Var(i) => column(*i),
+            Function::Const(c) => Array1::from_elem(n, *c),
+            Function::Add(f, g) => eval(f) + eval(g),
+            Function::Sub(f, g) => eval(f) - eval(g),
+            Function::Neg(f) => -eval(f),
+            Function::Mul(f, g) => eval(f) * eval(g),
+            Function::Div(f, g) => eval(f) / eval(g),
+            Function::Sin(f) => eval(f).mapv_into(f64::sin),
+            Function::Cos(f) => eval(f).mapv_into(f64::cos),
+            Function::Tan(f) => eval(f).mapv_into(f64::tan),
+            Function::Exp(f) => eval(f).mapv_into(f64::exp),
+            Function::Log(f) => eval(f).mapv_into(f64::ln),
+            Function::Sum(fs) => fs.iter().fold(Array1::zeros(n), |acc, f| acc + eval(f)),
+            Function::Prod(fs) => fs.iter().fold(Array1::ones(n), |acc, f| acc * eval(f)),
+            Function::PowI(f, k) => eval(f).mapv_into(|x| x.powi(*k)),
+            Function::Poly(coeffs) => column(0).mapv_into(|x| poly_eval(coeffs, x)),
+            Function::PolyF(fs, i) => {
+                // Horner's method, one coefficient function at a time
+                let x = column(*i);
                 fs.iter()
-                    .enumerate()
-                    .map(|(i, f)| f.eval(args) * x.powi(i as i32))
-                    .sum()
+                    .rev()
+                    .fold(Array1::zeros(n), |acc, f| acc * &x + eval(f))
             }
         }
     }
 
+    #[allow(dead_code)]
     pub fn sample(&self, interval: (f64, f64), steps: usize) -> Vec<(f64, f64)> {
         let delta = (interval.1 - interval.0) / steps as f64;
 
@@ -82,16 +177,328 @@ impl Function {
     }
 }
 
+// traversal -------------------------------------------------------------------
+
+impl Function {
+    /// rebuilds f with `g` applied to each direct subexpression, as the raw
+    /// variant (without the simplifications of the `fn_*` constructors)
+    pub fn map_children<G: FnMut(&Function) -> Function>(&self, mut g: G) -> Function {
+        let mut b = |f: &Function| Box::new(g(f));
+        match self {
+            Function::Var(_) | Function::Const(_) | Function::Poly(_) => self.clone(),
+            Function::Add(f1, f2) => Function::Add(b(f1), b(f2)),
+            Function::Sub(f1, f2) => Function::Sub(b(f1), b(f2)),
+            Function::Mul(f1, f2) => Function::Mul(b(f1), b(f2)),
+            Function::Div(f1, f2) => Function::Div(b(f1), b(f2)),
+            Function::Neg(f) => Function::Neg(b(f)),
+            Function::Sin(f) => Function::Sin(b(f)),
+            Function::Cos(f) => Function::Cos(b(f)),
+            Function::Tan(f) => Function::Tan(b(f)),
+            Function::Exp(f) => Function::Exp(b(f)),
+            Function::Log(f) => Function::Log(b(f)),
+            Function::PowI(f, n) => Function::PowI(b(f), *n),
+            Function::Sum(fs) => Function::Sum(fs.iter().map(|f| *b(f)).collect()),
+            Function::Prod(fs) => Function::Prod(fs.iter().map(|f| *b(f)).collect()),
+            Function::PolyF(fs, i) => Function::PolyF(fs.iter().map(|f| *b(f)).collect(), *i),
+        }
+    }
+
+    /// length of the args slice f reads from: one past the highest variable
+    /// index it uses (`Poly` uses x_0), 0 for constant expressions
+    pub fn num_vars(&self) -> usize {
+        match self {
+            Function::Var(i) => i + 1,
+            Function::Const(_) => 0,
+            Function::Poly(_) => 1,
+            Function::Add(f1, f2)
+            | Function::Sub(f1, f2)
+            | Function::Mul(f1, f2)
+            | Function::Div(f1, f2) => f1.num_vars().max(f2.num_vars()),
+            Function::Neg(f)
+            | Function::Sin(f)
+            | Function::Cos(f)
+            | Function::Tan(f)
+            | Function::Exp(f)
+            | Function::Log(f)
+            | Function::PowI(f, _) => f.num_vars(),
+            Function::Sum(fs) | Function::Prod(fs) => {
+                fs.iter().map(Function::num_vars).max().unwrap_or(0)
+            }
+            Function::PolyF(fs, i) => fs.iter().map(Function::num_vars).fold(i + 1, usize::max),
+        }
+    }
+
+    /// true if x_var occurs in f (`Poly` contains x_0), i.e. unless f is
+    /// constant in x_var by its structure
+    pub fn depends_on(&self, var: usize) -> bool {
+        match self {
+            Function::Var(i) => *i == var,
+            Function::Const(_) => false,
+            Function::Poly(_) => var == 0,
+            Function::Add(f1, f2)
+            | Function::Sub(f1, f2)
+            | Function::Mul(f1, f2)
+            | Function::Div(f1, f2) => f1.depends_on(var) || f2.depends_on(var),
+            Function::Neg(f)
+            | Function::Sin(f)
+            | Function::Cos(f)
+            | Function::Tan(f)
+            | Function::Exp(f)
+            | Function::Log(f)
+            | Function::PowI(f, _) => f.depends_on(var),
+            Function::Sum(fs) | Function::Prod(fs) => fs.iter().any(|f| f.depends_on(var)),
+            Function::PolyF(fs, i) => *i == var || fs.iter().any(|f| f.depends_on(var)),
+        }
+    }
+}
+
+// comparison ------------------------------------------------------------------
+
+// structural: constants are compared by bit pattern, so NaN == NaN and
+// 0 != -0, which makes equality an equivalence relation expressions can be
+// hashed and sorted by. see `is_equivalent` for equality as functions
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Function {}
+
+impl PartialOrd for Function {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// the canonical order: by variant first and then by contents, with constants
+/// compared by `f64::total_cmp`
+impl Ord for Function {
+    fn cmp(&self, other: &Self) -> Ordering {
+        use Function::*;
+
+        let by_variant = variant_rank(self).cmp(&variant_rank(other));
+        if by_variant != Ordering::Equal {
+            return by_variant;
+        }
+
+        let cmp_consts = |c1: &[f64], c2: &[f64]| {
+            c1.iter()
+                .zip(c2)
+                .map(|(x, y)| x.total_cmp(y))
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or_else(|| c1.len().cmp(&c2.len()))
+        };
+
+        match (self, other) {
+            (Const(c1), Const(c2)) => c1.total_cmp(c2),
+            (Var(i), Var(j)) => i.cmp(j),
+            (Poly(c1), Poly(c2)) => cmp_consts(c1, c2),
+            (PolyF(fs, i), PolyF(gs, j)) => i.cmp(j).then_with(|| fs.cmp(gs)),
+            (Sum(fs), Sum(gs)) | (Prod(fs), Prod(gs)) => fs.cmp(gs),
+            (PowI(f, n), PowI(g, m)) => f.cmp(g).then(n.cmp(m)),
+            (Exp(f), Exp(g))
+            | (Log(f), Log(g))
+            | (Sin(f), Sin(g))
+            | (Cos(f), Cos(g))
+            | (Tan(f), Tan(g))
+            | (Neg(f), Neg(g)) => f.cmp(g),
+            (Add(f1, f2), Add(g1, g2))
+            | (Sub(f1, f2), Sub(g1, g2))
+            | (Mul(f1, f2), Mul(g1, g2))
+            | (Div(f1, f2), Div(g1, g2)) => f1.cmp(g1).then_with(|| f2.cmp(g2)),
+            _ => unreachable!("variants of equal rank"),
+        }
+    }
+}
+
+impl Hash for Function {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        variant_rank(self).hash(state);
+        match self {
+            Function::Var(i) => i.hash(state),
+            Function::Const(c) => c.to_bits().hash(state),
+            Function::Poly(coeffs) => {
+                coeffs.len().hash(state);
+                for c in coeffs {
+                    c.to_bits().hash(state);
+                }
+            }
+            Function::PolyF(fs, i) => {
+                i.hash(state);
+                fs.hash(state);
+            }
+            Function::Sum(fs) | Function::Prod(fs) => fs.hash(state),
+            Function::PowI(f, n) => {
+                f.hash(state);
+                n.hash(state);
+            }
+            Function::Neg(f)
+            | Function::Sin(f)
+            | Function::Cos(f)
+            | Function::Tan(f)
+            | Function::Exp(f)
+            | Function::Log(f) => f.hash(state),
+            Function::Add(f1, f2)
+            | Function::Sub(f1, f2)
+            | Function::Mul(f1, f2)
+            | Function::Div(f1, f2) => {
+                f1.hash(state);
+                f2.hash(state);
+            }
+        }
+    }
+}
+
+/// position of each variant in the canonical order
+fn variant_rank(f: &Function) -> u8 {
+    match f {
+        Function::Const(_) => 0,
+        Function::Var(_) => 1,
+        Function::Poly(_) => 2,
+        Function::PolyF(_, _) => 3,
+        Function::Sum(_) => 4,
+        Function::Prod(_) => 5,
+        Function::PowI(_, _) => 6,
+        Function::Exp(_) => 7,
+        Function::Log(_) => 8,
+        Function::Sin(_) => 9,
+        Function::Cos(_) => 10,
+        Function::Tan(_) => 11,
+        Function::Add(_, _) => 12,
+        Function::Sub(_, _) => 13,
+        Function::Neg(_) => 14,
+        Function::Mul(_, _) => 15,
+        Function::Div(_, _) => 16,
+    }
+}
+
 impl EvaluateOne for Function {
     fn eval_one(&self, x: f64) -> f64 {
         self.eval([x])
     }
+
+    fn singularities(&self, interval: (f64, f64)) -> Vec<f64> {
+        let mut poles = Vec::new();
+        collect_poles(self, interval, &mut poles);
+
+        poles.retain(|&x| interval.0 < x && x < interval.1);
+        poles.sort_by(f64::total_cmp);
+        poles.dedup();
+        poles
+    }
+}
+
+impl EvaluateMany for Function {
+    fn eval_batch(&self, points: &ArrayView2<f64>) -> Array1<f64> {
+        Function::eval_batch(self, points)
+    }
+}
+
+// singularities ---------------------------------------------------------------
+
+/// upper bound on the number of poles a single subexpression may report,
+/// beyond that (e.g. tan(1000x) zoomed far out) they are not worth drawing
+const MAX_POLES: usize = 1000;
+
+/// walks the tree looking for the poles of f as a function of x_0 that can be
+/// found symbolically: zeros of `Div` denominators, of bases with negative
+/// powers, and the points where the argument of `Tan` hits pi/2 + k*pi
+fn collect_poles(f: &Function, interval: (f64, f64), poles: &mut Vec<f64>) {
+    match f {
+        Function::Var(_) | Function::Const(_) | Function::Poly(_) => {}
+        Function::Div(f1, f2) => {
+            collect_poles(f1, interval, poles);
+            collect_poles(f2, interval, poles);
+            collect_zeros(f2, poles);
+        }
+        Function::PowI(g, n) => {
+            collect_poles(g, interval, poles);
+            if *n < 0 {
+                collect_zeros(g, poles);
+            }
+        }
+        Function::Tan(g) => {
+            collect_poles(g, interval, poles);
+
+            // a*x + b = pi/2 + k*pi  <=>  x = (pi/2 + k*pi - b) / a
+            if let Some((a, b)) = as_affine(g) {
+                let pi = std::f64::consts::PI;
+                let k_at = |x: f64| (a * x + b - pi / 2.0) / pi;
+                let (k0, k1) = (k_at(interval.0), k_at(interval.1));
+                let (k_min, k_max) = (k0.min(k1).floor(), k0.max(k1).ceil());
+
+                if k_max - k_min <= MAX_POLES as f64 {
+                    let mut k = k_min;
+                    while k <= k_max {
+                        poles.push((pi / 2.0 + k * pi - b) / a);
+                        k += 1.0;
+                    }
+                }
+            }
+        }
+        Function::Add(f1, f2) | Function::Sub(f1, f2) | Function::Mul(f1, f2) => {
+            collect_poles(f1, interval, poles);
+            collect_poles(f2, interval, poles);
+        }
+        Function::Neg(g)
+        | Function::Sin(g)
+        | Function::Cos(g)
+        | Function::Exp(g)
+        | Function::Log(g) => collect_poles(g, interval, poles),
+        Function::Sum(fs) | Function::Prod(fs) | Function::PolyF(fs, _) => {
+            for g in fs {
+                collect_poles(g, interval, poles);
+            }
+        }
+    }
+}
+
+/// pushes the zeros of f (as a function of x_0) that can be read off the tree:
+/// affine expressions and products or positive powers of them. other factors
+/// that are rational functions (e.g. a `Poly`) have theirs located exactly
+fn collect_zeros(f: &Function, zeros: &mut Vec<f64>) {
+    if let Some((a, b)) = as_affine(f) {
+        if a != 0.0 {
+            zeros.push(-b / a);
+        }
+        return;
+    }
+
+    match f {
+        Function::Neg(g) => collect_zeros(g, zeros),
+        Function::PowI(g, n) if *n > 0 => collect_zeros(g, zeros),
+        Function::Mul(f1, f2) => {
+            collect_zeros(f1, zeros);
+            collect_zeros(f2, zeros);
+        }
+        Function::Prod(fs) => {
+            for g in fs {
+                collect_zeros(g, zeros);
+            }
+        }
+        // the poles of the reciprocal, i.e. the real roots of the numerator
+        _ => {
+            if let Some(reciprocal) = Rational::from_function(f).and_then(|r| r.powi(-1)) {
+                zeros.extend(reciprocal.poles().into_iter().map(|(x, _)| x));
+            }
+        }
+    }
+}
+
+/// returns (a, b) if f(x) = a*x_0 + b, detected by the derivative being constant
+fn as_affine(f: &Function) -> Option<(f64, f64)> {
+    match fn_pdv(f, 0) {
+        Function::Const(a) => Some((a, f.eval([0.0]))),
+        _ => None,
+    }
 }
 
 // utility ---------------------------------------------------------------------
 
-fn get_arg(args: &[f64], i: usize) -> f64 {
-    args.get(i).copied().unwrap_or(0.0)
+fn get_arg<S: Scalar>(args: &[S], i: usize) -> S {
+    args.get(i).cloned().unwrap_or_else(S::zero)
 }
 
 impl From<f64> for Function {
@@ -303,6 +710,7 @@ pub fn fn_poly(coeffs: Vec<f64>) -> Function {
 }
 
 /// returns the function sum_k f_k(x)*x^k where fs = [f0,...,fn] and x = x_i
+#[allow(dead_code)]
 pub fn fn_poly_with_roots(roots: &[f64]) -> Function {
     roots
         .iter()
@@ -310,7 +718,6 @@ pub fn fn_poly_with_roots(roots: &[f64]) -> Function {
         .fold(fn_const(1.0), fn_mul)
 }
 
-
 // =============================================================================
 
 /// symbolically computes the partial derivative of f with respect to the i-th variable
@@ -352,7 +759,7 @@ pub fn fn_pdv(f: &Function, i: usize) -> Function {
             for (j, f) in fs.iter().enumerate() {
                 let mut factors = fs.clone();
                 factors.remove(j);
-                summands.push(fn_mul(f.clone(), fn_prod(factors)));
+                summands.push(fn_mul(fn_pdv(f, i), fn_prod(factors)));
             }
             fn_sum(summands)
         }
@@ -360,18 +767,10 @@ pub fn fn_pdv(f: &Function, i: usize) -> Function {
             fn_const(*n as f64),
             fn_mul(fn_powi(*f.clone(), *n - 1), fn_pdv(f, i)),
         ),
-        Function::Poly(coeffs) => {
-            let mut new_coeffs = Vec::new();
-            for (k, c) in coeffs.iter().enumerate() {
-                // looking at term cx^k (i.e., c = c_k)
-                if k == 0 {
-                    continue;
-                }
-                // pdv(cx^k) = (c*k)x^(k-1)
-                new_coeffs.push(c * (k as f64));
-            }
-            fn_poly(new_coeffs)
-        }
+        // polynomials are in x_0, so constant in every other variable
+        Function::Poly(_) if i != 0 => fn_const(0.0),
+        // pdv(cx^k) = (c*k)x^(k-1)
+        Function::Poly(coeffs) => fn_poly(poly_derivative(coeffs)),
         Function::PolyF(fs, j) => {
             let mut terms = Vec::new();
             for (k, f) in fs.iter().enumerate() {
@@ -395,9 +794,77 @@ pub fn fn_pdv(f: &Function, i: usize) -> Function {
 }
 
 // could use similar recursive structure for other symbolic manipulation:
-// - function simplification (generally replacing subexpressions)
 // - displaying with latex
-// - fixing variables (given f(x, y), can obtain function f(x) with y fixed to some constant)
+
+// substitution ----------------------------------------------------------------
+
+impl Function {
+    /// returns f with every occurrence of x_var replaced by g, including the
+    /// variable of `PolyF` and the implicit x_0 of `Poly`
+    ///
+    /// the result is rebuilt with the `fn_*` constructors, so substituting
+    /// constants folds wherever possible. g itself is not substituted into,
+    /// e.g. x_0 + x_1 with x_0 := x_1 gives x_1 + x_1
+    pub fn substitute(&self, var: usize, g: &Function) -> Function {
+        let sub = |f: &Function| f.substitute(var, g);
+        match self {
+            Function::Var(i) if *i == var => g.clone(),
+            Function::Var(_) | Function::Const(_) => self.clone(),
+            Function::Add(f1, f2) => fn_add(sub(f1), sub(f2)),
+            Function::Sub(f1, f2) => fn_sub(sub(f1), sub(f2)),
+            Function::Neg(f) => fn_neg(sub(f)),
+            Function::Mul(f1, f2) => fn_mul(sub(f1), sub(f2)),
+            Function::Div(f1, f2) => fn_div(sub(f1), sub(f2)),
+            Function::Sin(f) => fn_sin(sub(f)),
+            Function::Cos(f) => fn_cos(sub(f)),
+            Function::Tan(f) => fn_tan(sub(f)),
+            Function::Exp(f) => fn_exp(sub(f)),
+            Function::Log(f) => fn_log(sub(f)),
+            Function::Sum(fs) => fn_sum(fs.iter().map(sub).collect()),
+            Function::Prod(fs) => fn_prod(fs.iter().map(sub).collect()),
+            Function::PowI(f, n) => fn_powi(sub(f), *n),
+            // polynomials are in x_0
+            Function::Poly(_) if var != 0 => self.clone(),
+            Function::Poly(coeffs) => match (g, Polynomial::from_function(g)) {
+                (Function::Var(0), _) => self.clone(),
+                // polynomials of polynomials (constants included) stay one
+                (_, Some(inner)) => Polynomial::new(coeffs.clone())
+                    .compose(&inner)
+                    .to_function(),
+                _ => horner(coeffs.iter().map(|&c| fn_const(c)).collect(), g),
+            },
+            Function::PolyF(fs, i) => {
+                let fs = fs.iter().map(sub).collect();
+                match g {
+                    _ if *i != var => Function::PolyF(fs, *i),
+                    // renaming the variable keeps the polynomial structure
+                    Function::Var(j) => Function::PolyF(fs, *j),
+                    _ => horner(fs, g),
+                }
+            }
+        }
+    }
+
+    /// returns f with x_var fixed to the value, e.g. the slice f(x, 2) of f(x, y)
+    /// is `f.fix(1, 2.0)`
+    pub fn fix(&self, var: usize, value: f64) -> Function {
+        self.substitute(var, &fn_const(value))
+    }
+
+    /// returns the composition (f∘g)(x) := f(g(x)) of the univariate f with g,
+    /// i.e. g substituted for x_0
+    pub fn compose(&self, g: &Function) -> Function {
+        self.substitute(0, g)
+    }
+}
+
+/// returns the function f0 + x*(f1 + x*(f2 + ...)) where fs = [f0,...,fn]
+fn horner(fs: Vec<Function>, x: &Function) -> Function {
+    fs.into_iter()
+        .rev()
+        .reduce(|acc, f| fn_add(f, fn_mul(acc, x.clone())))
+        .unwrap_or_else(|| fn_const(0.0))
+}
 
 // =============================================================================
 
@@ -467,6 +934,8 @@ impl std::fmt::Display for Function {
 mod test {
     use std::vec;
 
+    use rand::Rng;
+
     use crate::util::sample_interval_equidistributed;
 
     use super::*;
@@ -494,4 +963,238 @@ mod test {
             println!("f({}) = {}", x, y);
         }
     }
+
+    #[test]
+    fn test_singularities() {
+        let pi = std::f64::consts::PI;
+        let cases = [
+            (
+                fn_tan(X),
+                vec![-3.0 * pi / 2.0, -pi / 2.0, pi / 2.0, 3.0 * pi / 2.0],
+            ),
+            (
+                fn_div(
+                    fn_const(1.0),
+                    fn_sub(fn_mul(fn_const(2.0), X), fn_const(1.0)),
+                ),
+                vec![0.5],
+            ),
+            (fn_div(X, fn_powi(fn_add(X, fn_const(1.0)), 2)), vec![-1.0]),
+            (
+                fn_powi(fn_mul(X, fn_sub(X, fn_const(3.0))), -1),
+                vec![0.0, 3.0],
+            ),
+            (fn_sin(X), vec![]),
+            (fn_div(X, fn_poly(vec![-4.0, 0.0, 1.0])), vec![-2.0, 2.0]),
+            (
+                fn_powi(
+                    fn_mul(fn_poly(vec![1.0, 0.0, 1.0]), fn_poly(vec![3.0, 1.0])),
+                    -1,
+                ),
+                vec![-3.0],
+            ),
+            (fn_powi(fn_add(X, fn_const(1.0)), -60), vec![-1.0]),
+        ];
+
+        for (f, expected) in cases {
+            let poles = f.singularities((-5.0, 5.0));
+            assert_eq!(poles.len(), expected.len(), "{f}: {poles:?}");
+            for (p, e) in poles.iter().zip(expected) {
+                assert!((p - e).abs() < 1e-12, "{f}: {poles:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_complex() {
+        let i = Complex64::i();
+
+        // e^(i*pi) = -1
+        let f = fn_exp(fn_mul(fn_const(std::f64::consts::PI), X));
+        assert!((f.eval_complex([i]) + 1.0).norm() < 1e-12);
+
+        // principal branch: log(-1) = i*pi
+        assert!(
+            (fn_log(X).eval_complex([Complex64::from(-1.0)]) - i * std::f64::consts::PI).norm()
+                < 1e-12
+        );
+
+        // z^2 + 1 has roots at +-i, also as a polynomial
+        for f in [
+            fn_add(fn_powi(X, 2), fn_const(1.0)),
+            fn_poly(vec![1.0, 0.0, 1.0]),
+        ] {
+            assert!(f.eval_complex([i]).norm() < 1e-12);
+            assert!(f.eval_complex([-i]).norm() < 1e-12);
+        }
+
+        // agrees with real evaluation on the real axis
+        let f = fn_div(fn_sin(X), fn_sub(fn_powi(X, -2), fn_tan(X)));
+        for x in [-2.0, 0.3, 1.7] {
+            assert!((f.eval_complex([Complex64::from(x)]).re - f.eval([x])).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_eq_hash() {
+        use std::collections::HashSet;
+
+        let f = fn_add(fn_sin(X), fn_mul(fn_const(2.0), Y));
+        assert_eq!(f, f.clone());
+        assert_ne!(f, fn_add(fn_mul(fn_const(2.0), Y), fn_sin(X)));
+
+        // constants by bit pattern
+        assert_eq!(fn_const(f64::NAN), fn_const(f64::NAN));
+        assert_ne!(fn_const(0.0), fn_const(-0.0));
+
+        // variants are ordered by rank first, constants before variables
+        assert!(fn_const(100.0) < X);
+        assert!(X < Y);
+        assert!(fn_powi(X, 2) < fn_powi(X, 3));
+
+        let set: HashSet<Function> = [f.clone(), fn_sin(X), f.clone(), fn_sin(X)].into();
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&fn_sin(X)));
+    }
+
+    #[test]
+    fn test_eval_batch() {
+        let functions = [
+            fn_add(fn_sin(X), fn_mul(fn_const(2.0), Y)),
+            fn_div(fn_exp(fn_neg(X)), fn_sub(fn_tan(Y), fn_log(X))),
+            fn_sum(vec![fn_powi(X, -3), fn_prod(vec![X, fn_cos(Y), X])]),
+            fn_poly(vec![1.0, -2.0, 0.5, 0.25]),
+            Function::PolyF(vec![fn_sin(Y), X, fn_const(3.0)], 1),
+            // x_2 is not a column, so reads as 0
+            fn_add(X, fn_cos(Z)),
+        ];
+
+        let mut rng = rand::thread_rng();
+        let points = ndarray::Array2::from_shape_simple_fn((100, 2), || rng.gen_range(0.1..3.0));
+
+        for f in &functions {
+            let batch = f.eval_batch(&points.view());
+            for (row, &b) in points.rows().into_iter().zip(&batch) {
+                let expected: f64 = f.eval(row.to_vec());
+                assert!(
+                    (b - expected).abs() <= 1e-12 * (1.0 + expected.abs()),
+                    "{f}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_generic() {
+        let f: Function = "x^3 - exp(x) + sin(10x)/4 + log(y) * tan(x)"
+            .parse()
+            .unwrap();
+        let (x, y) = (0.3, 1.5);
+
+        let expected: f64 = f.eval([x, y]);
+
+        let single: f32 = f.eval([x as f32, y as f32]);
+        assert!((single as f64 - expected).abs() < 1e-5);
+
+        let complex = f.eval([num_complex::Complex32::from(x as f32), (y as f32).into()]);
+        assert!((complex.re as f64 - expected).abs() < 1e-5 && complex.im == 0.0);
+    }
+
+    #[test]
+    fn test_eval_with_gradient() {
+        let mut rng = rand::thread_rng();
+
+        // one function per variant, in two variables, all defined for x, y in [-1, 1]
+        let functions: Vec<Function> = vec![
+            X,
+            fn_const(2.5),
+            fn_add(fn_mul(X, Y), Y),
+            fn_sub(fn_powi(X, 2), Y),
+            fn_neg(fn_mul(X, Y)),
+            fn_mul(fn_sin(X), fn_exp(Y)),
+            fn_div(fn_add(X, Y), fn_add(fn_powi(Y, 2), fn_const(2.0))),
+            fn_sin(fn_mul(X, Y)),
+            fn_cos(fn_sub(X, fn_powi(Y, 3))),
+            fn_tan(fn_mul(fn_const(0.5), fn_add(X, Y))),
+            fn_exp(fn_mul(X, fn_powi(Y, 2))),
+            fn_log(fn_add(fn_add(fn_powi(X, 2), fn_powi(Y, 2)), fn_const(1.0))),
+            fn_sum(vec![X, fn_mul(X, Y), fn_sin(Y)]),
+            fn_prod(vec![X, fn_cos(Y), fn_add(X, fn_const(3.0))]),
+            fn_powi(fn_add(X, fn_mul(fn_const(2.0), Y)), -3),
+            fn_poly(vec![1.0, -2.0, 0.5, 3.0]),
+            Function::PolyF(vec![Y, fn_sin(Y), fn_powi(Y, 2)], 0),
+            Function::PolyF(vec![X, fn_const(2.0)], 1),
+        ];
+
+        for f in &functions {
+            let grad_fns = [fn_pdv(f, 0), fn_pdv(f, 1)];
+
+            for _ in 0..20 {
+                let args = [rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)];
+                let (value, grad) = f.eval_with_gradient(args);
+
+                // skip the rare sample right next to a pole
+                if !value.is_finite() || value.abs() > 1e6 {
+                    continue;
+                }
+
+                assert!((value - f.eval(args)).abs() < 1e-12, "{f}");
+                for (k, df) in grad_fns.iter().enumerate() {
+                    let expected: f64 = df.eval(args);
+                    let tol = 1e-9 * expected.abs().max(1.0);
+                    assert!(
+                        (grad[k] - expected).abs() < tol,
+                        "d/dx_{k} {f} at {args:?}: {} != {expected}",
+                        grad[k]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_substitute() {
+        let f = fn_add(fn_mul(fn_sin(X), Y), fn_poly(vec![1.0, 0.0, 2.0]));
+        let g = fn_add(fn_exp(Y), fn_const(0.5));
+
+        for (x, y) in [(0.3, -1.2), (-2.0, 0.7), (1.5, 1.5)] {
+            // slices
+            let expected: f64 = f.eval([x, y]);
+            assert!((f.fix(1, y).eval([x]) - expected).abs() < 1e-12);
+            assert!((f.fix(0, x).eval([0.0, y]) - expected).abs() < 1e-12);
+
+            // f(g(x, y), y), through the x_0 of Poly
+            let gx: f64 = g.eval([x, y]);
+            let expected: f64 = f.eval([gx, y]);
+            assert!((f.substitute(0, &g).eval([x, y]) - expected).abs() < 1e-12);
+
+            // variable and coefficients of PolyF
+            let p = Function::PolyF(vec![fn_cos(X), Y, fn_const(3.0)], 1);
+            let expected: f64 = p.eval([x, gx]);
+            assert!((p.substitute(1, &g).eval([x, y]) - expected).abs() < 1e-12);
+            let expected: f64 = p.eval([gx, y]);
+            assert!((p.compose(&g).eval([x, y]) - expected).abs() < 1e-12);
+        }
+
+        // fixing everything folds to a constant
+        assert_eq!(f.fix(0, 0.0).fix(1, 5.0), fn_const(1.0));
+        assert_eq!(
+            fn_poly(vec![1.0, 2.0]).compose(&fn_const(3.0)),
+            fn_const(7.0)
+        );
+        // renaming keeps the structure
+        assert_eq!(
+            Function::PolyF(vec![X, Y], 1).substitute(1, &Z),
+            Function::PolyF(vec![X, Z], 2)
+        );
+        assert_eq!(
+            fn_sin(X).compose(&fn_poly(vec![0.0, 2.0])),
+            fn_sin(fn_poly(vec![0.0, 2.0]))
+        );
+        // (x + 1)^2 + 1
+        assert_eq!(
+            fn_poly(vec![1.0, 0.0, 1.0]).compose(&fn_poly(vec![1.0, 1.0])),
+            fn_poly(vec![2.0, 2.0, 1.0])
+        );
+    }
 }
@@ -1,15 +1,22 @@
-use crate::{fn_mul, Function};
+use crate::antiderivative::fn_integrate;
+use crate::parallel::sum_indexed;
+use crate::{fn_mul, EvaluateOne, Function};
 
 // methods =====================================================================
 
 #[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
 pub enum IntMethod {
     Midpoint(usize),
     Trapezoidal(usize),
     CompositeTrapezoidal(usize),
 }
 
-pub fn integrate(f: &Function, interval: (f64, f64), method: IntMethod) -> f64 {
+pub fn integrate<F: EvaluateOne + Sync + ?Sized>(
+    f: &F,
+    interval: (f64, f64),
+    method: IntMethod,
+) -> f64 {
     match method {
         IntMethod::Midpoint(n) => int_midpoint(f, interval, n),
         IntMethod::Trapezoidal(n) => int_trapezoidal(f, interval, n),
@@ -21,65 +28,81 @@ pub fn integrate(f: &Function, interval: (f64, f64), method: IntMethod) -> f64 {
 
 /// computes the integral of f over the interval [a, b] using the midpoint rule
 /// with n subintervals
-pub fn int_midpoint(f: &Function, (a, b): (f64, f64), n: usize) -> f64 {
+pub fn int_midpoint<F: EvaluateOne + Sync + ?Sized>(f: &F, (a, b): (f64, f64), n: usize) -> f64 {
     // width of each subinterval
     let delta = (b - a) / (n as f64);
 
     // sample point in the middle of subinterval
     let x_0 = a + delta / 2.0;
 
-    let mut sum = 0.0;
-    for i in 0..n {
-        let x = x_0 + delta * (i as f64);
-        sum += f.eval([x]) * delta;
-    }
-    sum
+    sum_indexed(n, |i| f.eval_one(x_0 + delta * (i as f64))) * delta
 }
 
 /// computes the integral of f over the interval [a, b] using the trapezoidal rule
 /// with n subintervals
-pub fn int_trapezoidal(f: &Function, (a, b): (f64, f64), n: usize) -> f64 {
+pub fn int_trapezoidal<F: EvaluateOne + Sync + ?Sized>(f: &F, (a, b): (f64, f64), n: usize) -> f64 {
     // width of each subinterval
     let delta = (b - a) / (n as f64);
 
-    let mut sum = 0.0;
     // i = 0, 1, ..., n - 1 (since we calculate x_i and x_{i+1})
-    for i in 0..n {
+    let sum = sum_indexed(n, |i| {
         // left and right endpoints of subinterval
         let x0 = a + delta * (i as f64);
         let x1 = a + delta * ((i + 1) as f64);
 
         // area of trapezoid = (f(x_0) + f(x_1)) * delta / 2
-        sum += (f.eval([x0]) + f.eval([x1])) * delta / 2.0;
-    }
-    sum
+        (f.eval_one(x0) + f.eval_one(x1)) / 2.0
+    });
+    sum * delta
 }
 
 /// computes the integral of f over the interval [a, b] using the composite trapezoidal rule
 /// with n subintervals
-pub fn int_composite_trapezoidal(f: &Function, (a, b): (f64, f64), n: usize) -> f64 {
+pub fn int_composite_trapezoidal<F: EvaluateOne + Sync + ?Sized>(
+    f: &F,
+    (a, b): (f64, f64),
+    n: usize,
+) -> f64 {
     // width of each subinterval
     let delta = (b - a) / (n as f64);
 
-    let mut sum = 0.0;
-
     // i = 0, 1, ..., n
-    for i in 0..=n {
+    let sum = sum_indexed(n + 1, |i| {
         // f(x_i) where x_i = a + delta * i
-        let fx = f.eval([a + delta * (i as f64)]);
+        let fx = f.eval_one(a + delta * (i as f64));
 
-        let contribution = if i == 0 || i == n {
+        if i == 0 || i == n {
             // left and right of total interval only counted once
             fx / 2.0
         } else {
             // all other points counted twice
             fx
-        };
+        }
+    });
 
-        sum += contribution * delta;
-    }
+    sum * delta
+}
 
-    sum
+// =============================================================================
+
+/// computes the integral of f over the interval [a, b] as F(b) - F(a) from
+/// the symbolic antiderivative F (see `fn_integrate`), or None if there is none
+///
+/// exact up to rounding, as long as f is continuous on [a, b]
+pub fn int_antiderivative(f: &Function, (a, b): (f64, f64)) -> Option<f64> {
+    let antiderivative = fn_integrate(f, 0)?;
+    let (fa, fb): (f64, f64) = (antiderivative.eval([a]), antiderivative.eval([b]));
+    Some(fb - fa)
+}
+
+/// error of the numeric integral of f over the interval by the method,
+/// against `int_antiderivative` as the reference
+///
+/// cross-checks both: a large error means too few subintervals, or an
+/// antiderivative that doesn't hold on the whole interval
+pub fn int_error(f: &Function, interval: (f64, f64), method: IntMethod) -> Option<f64> {
+    let exact = int_antiderivative(f, interval)?;
+    Some(integrate(f, interval, method) - exact)
 }
 
 // =============================================================================
@@ -90,7 +113,8 @@ pub fn int_inner_product(
     interval: (f64, f64),
     method: IntMethod,
 ) -> f64 {
-    let f_times_g = fn_mul(f.clone(), g.clone());
+    // compiled, since the integration rules evaluate it many times
+    let f_times_g = fn_mul(f.clone(), g.clone()).compile();
     integrate(&f_times_g, interval, method)
 }
 
@@ -150,4 +174,27 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_int_antiderivative() {
+        // f(x) = x^2 * sin(x), F(x) = (2 - x^2) cos(x) + 2x sin(x)
+        let f = fn_mul(fn_powi(X, 2), fn_sin(X));
+        let exact = int_antiderivative(&f, (0.0, 2.0)).unwrap();
+        assert!((exact - (-2.0 * 2f64.cos() + 4.0 * 2f64.sin() - 2.0)).abs() < 1e-12);
+
+        for f in [
+            f,
+            fn_exp(fn_neg(X)),
+            fn_div(X, fn_add(fn_powi(X, 2), fn_const(1.0))),
+        ] {
+            let error = int_error(&f, (0.0, 2.0), IntMethod::CompositeTrapezoidal(1000)).unwrap();
+            assert!(error.abs() < 1e-5, "{f}: {error}");
+        }
+
+        // no antiderivative to check against
+        assert_eq!(
+            int_antiderivative(&fn_exp(fn_neg(fn_powi(X, 2))), (0.0, 1.0)),
+            None
+        );
+    }
 }
@@ -0,0 +1,496 @@
+use std::f64::consts::{PI, TAU};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// -----------------------------------------------------------------------------
+
+use crate::func::Function;
+use crate::scalar::Scalar;
+
+// =============================================================================
+
+/// closed interval [lo, hi] of reals, for evaluating functions rigorously: the
+/// result of every operation contains every possible result of the operation
+/// on members of the operands
+///
+/// bounds are rounded outward so floating point error can't shrink an
+/// enclosure. intervals with lo > hi (or NaN bounds) are empty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub const EMPTY: Interval = Interval {
+        lo: f64::INFINITY,
+        hi: f64::NEG_INFINITY,
+    };
+
+    pub const ENTIRE: Interval = Interval {
+        lo: f64::NEG_INFINITY,
+        hi: f64::INFINITY,
+    };
+
+    pub fn new(lo: f64, hi: f64) -> Self {
+        Interval { lo, hi }
+    }
+
+    pub fn point(x: f64) -> Self {
+        Interval { lo: x, hi: x }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo > self.hi || self.lo.is_nan() || self.hi.is_nan()
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn midpoint(&self) -> f64 {
+        self.lo + (self.hi - self.lo) / 2.0
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.lo.is_finite() && self.hi.is_finite()
+    }
+
+    pub fn bisect(&self) -> (Interval, Interval) {
+        let m = self.midpoint();
+        (Interval::new(self.lo, m), Interval::new(m, self.hi))
+    }
+
+    /// smallest interval containing both
+    pub fn hull(&self, other: &Interval) -> Interval {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        Interval::new(self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    /// widens the bounds by `ulps` units in the last place each way
+    ///
+    /// beyond `MAX_STEPPED_ULPS` the bounds are instead moved by a relative
+    /// amount at least as large, so the cost doesn't grow with `ulps`
+    fn round_out(lo: f64, hi: f64, ulps: u32) -> Interval {
+        if ulps > MAX_STEPPED_ULPS {
+            return Interval {
+                lo: (lo - Interval::ulps_bound(lo, ulps)).next_down(),
+                hi: (hi + Interval::ulps_bound(hi, ulps)).next_up(),
+            };
+        }
+        let (mut lo, mut hi) = (lo, hi);
+        for _ in 0..ulps {
+            lo = lo.next_down();
+            hi = hi.next_up();
+        }
+        Interval { lo, hi }
+    }
+
+    /// upper bound of `ulps` units in the last place of x: an ulp is at most
+    /// |x| * EPSILON for normal numbers and the smallest subnormal otherwise.
+    /// one extra ulp covers the rounding of the bound itself
+    fn ulps_bound(x: f64, ulps: u32) -> f64 {
+        let n = ulps as f64;
+        x.abs() * f64::EPSILON * (n + 1.0) + n * f64::from_bits(1)
+    }
+
+    /// hull of the given (finite number of) candidate bounds
+    fn from_candidates(values: &[f64], ulps: u32) -> Interval {
+        let lo = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let hi = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Interval::round_out(lo, hi, ulps)
+    }
+
+    /// true if a + 2k*pi lies in the interval for some integer k, erring on the
+    /// side of yes since pi itself is rounded
+    fn contains_periodic(&self, a: f64, period: f64) -> bool {
+        let slack = 1e-12 * (1.0 + self.lo.abs().max(self.hi.abs()));
+        let k_min = ((self.lo - slack - a) / period).ceil();
+        let k_max = ((self.hi + slack - a) / period).floor();
+        k_min <= k_max
+    }
+}
+
+/// ulps added to the bounds of results from the (not correctly rounded)
+/// library functions
+const LIBM_ULPS: u32 = 2;
+
+/// widenings up to this many ulps step through the floats one at a time
+const MAX_STEPPED_ULPS: u32 = 16;
+
+// arithmetic ------------------------------------------------------------------
+
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return Interval::EMPTY;
+        }
+        Interval::round_out(self.lo + other.lo, self.hi + other.hi, 1)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return Interval::EMPTY;
+        }
+        Interval::round_out(self.lo - other.hi, self.hi - other.lo, 1)
+    }
+}
+
+impl Neg for Interval {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Interval::new(-self.hi, -self.lo)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return Interval::EMPTY;
+        }
+        // 0 * inf is taken as 0, the limit of the bounded members
+        let mul = |a: f64, b: f64| if a == 0.0 || b == 0.0 { 0.0 } else { a * b };
+        Interval::from_candidates(
+            &[
+                mul(self.lo, other.lo),
+                mul(self.lo, other.hi),
+                mul(self.hi, other.lo),
+                mul(self.hi, other.hi),
+            ],
+            1,
+        )
+    }
+}
+
+impl Div for Interval {
+    type Output = Self;
+
+    /// denominators containing zero give the entire real line
+    fn div(self, other: Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return Interval::EMPTY;
+        }
+        if other.contains(0.0) {
+            return Interval::ENTIRE;
+        }
+        let reciprocal = Interval::round_out(1.0 / other.hi, 1.0 / other.lo, 1);
+        self * reciprocal
+    }
+}
+
+// elementary functions --------------------------------------------------------
+
+impl Scalar for Interval {
+    fn from_f64(c: f64) -> Self {
+        Interval::point(c)
+    }
+
+    /// uses that sin reaches 1 at pi/2 + 2k*pi and -1 at -pi/2 + 2k*pi, and is
+    /// monotonic in between
+    fn sin(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        if self.width() >= TAU || !self.is_bounded() {
+            return Interval::new(-1.0, 1.0);
+        }
+        let bounds = Interval::from_candidates(&[self.lo.sin(), self.hi.sin()], LIBM_ULPS);
+        let hi = if self.contains_periodic(PI / 2.0, TAU) {
+            1.0
+        } else {
+            bounds.hi.min(1.0)
+        };
+        let lo = if self.contains_periodic(-PI / 2.0, TAU) {
+            -1.0
+        } else {
+            bounds.lo.max(-1.0)
+        };
+        Interval::new(lo, hi)
+    }
+
+    /// uses that cos reaches 1 at 2k*pi and -1 at pi + 2k*pi, and is
+    /// monotonic in between
+    fn cos(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        if self.width() >= TAU || !self.is_bounded() {
+            return Interval::new(-1.0, 1.0);
+        }
+        let bounds = Interval::from_candidates(&[self.lo.cos(), self.hi.cos()], LIBM_ULPS);
+        let hi = if self.contains_periodic(0.0, TAU) {
+            1.0
+        } else {
+            bounds.hi.min(1.0)
+        };
+        let lo = if self.contains_periodic(PI, TAU) {
+            -1.0
+        } else {
+            bounds.lo.max(-1.0)
+        };
+        Interval::new(lo, hi)
+    }
+
+    /// increasing between the poles at pi/2 + k*pi, entire if one is inside
+    fn tan(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        if self.width() >= PI || !self.is_bounded() || self.contains_periodic(PI / 2.0, PI) {
+            return Interval::ENTIRE;
+        }
+        Interval::round_out(self.lo.tan(), self.hi.tan(), LIBM_ULPS)
+    }
+
+    fn exp(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        let y = Interval::round_out(self.lo.exp(), self.hi.exp(), LIBM_ULPS);
+        Interval::new(y.lo.max(0.0), y.hi)
+    }
+
+    /// only the part of the interval inside the domain (0, inf) contributes,
+    /// an interval entirely outside it gives the empty interval
+    fn ln(self) -> Self {
+        if self.is_empty() || self.hi < 0.0 {
+            return Interval::EMPTY;
+        }
+        Interval::round_out(self.lo.max(0.0).ln(), self.hi.ln(), LIBM_ULPS)
+    }
+
+    /// f64::powi multiplies repeatedly, so its error grows with n
+    fn powi(self, n: i32) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        if n < 0 {
+            // -i32::MIN overflows, so split off one factor
+            let denominator = match n.checked_neg() {
+                Some(m) => self.powi(m),
+                None => self.powi(i32::MAX) * self,
+            };
+            return Interval::point(1.0) / denominator;
+        }
+        if n == 0 {
+            return Interval::point(1.0);
+        }
+        let ulps = LIBM_ULPS + n as u32;
+        if n % 2 == 0 && self.contains(0.0) {
+            let m = self.lo.abs().max(self.hi.abs());
+            let hi = Interval::round_out(m.powi(n), m.powi(n), ulps).hi;
+            return Interval::new(0.0, hi);
+        }
+        Interval::from_candidates(&[self.lo.powi(n), self.hi.powi(n)], ulps)
+    }
+}
+
+// evaluation ==================================================================
+
+impl Function {
+    /// returns an interval guaranteed to contain f(x) for every x in the box
+    /// given by `args`
+    pub fn eval_interval<T: AsRef<[Interval]>>(&self, args: T) -> Interval {
+        self.eval(args)
+    }
+}
+
+// root isolation ==============================================================
+
+/// an interval that may contain roots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RootEnclosure {
+    pub interval: Interval,
+    /// true if f is bounded on the interval and changes sign across it, so by
+    /// continuity it certainly contains a root
+    pub verified: bool,
+}
+
+/// finds intervals of width at most `tol` that together contain every root
+/// of f (in x_0) inside `interval`
+///
+/// parts of the interval are discarded only when interval evaluation proves
+/// f has no zero there, so no root is ever missed. adjacent candidates are
+/// merged, so the result is sorted and disjoint. intervals too narrow to be
+/// split any further in floating point are kept even if wider than `tol`
+///
+/// panics if `tol` is not positive
+pub fn isolate_roots(f: &Function, interval: (f64, f64), tol: f64) -> Vec<RootEnclosure> {
+    assert!(
+        tol > 0.0,
+        "root isolation tolerance must be positive, got {tol}"
+    );
+
+    let mut candidates = Vec::new();
+    let mut stack = vec![Interval::new(interval.0, interval.1)];
+
+    while let Some(x) = stack.pop() {
+        let y = f.eval_interval([x]);
+        if y.is_empty() || !y.contains(0.0) {
+            continue;
+        }
+        let (left, right) = x.bisect();
+        // the midpoint of adjacent floats is one of them
+        if x.width() <= tol || left.hi == x.lo || left.hi == x.hi {
+            candidates.push(x);
+            continue;
+        }
+        // push the right half first so candidates come out left to right
+        stack.push(right);
+        stack.push(left);
+    }
+
+    let mut merged: Vec<Interval> = Vec::new();
+    for x in candidates {
+        match merged.last_mut() {
+            Some(last) if last.hi >= x.lo => *last = last.hull(&x),
+            _ => merged.push(x),
+        }
+    }
+
+    merged
+        .into_iter()
+        .map(|x| {
+            let (a, b) = (f.eval([x.lo]), f.eval([x.hi]));
+            let bounded = f.eval_interval([x]).is_bounded();
+            RootEnclosure {
+                interval: x,
+                verified: bounded && a * b < 0.0,
+            }
+        })
+        .collect()
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::func::*;
+
+    /// checks the enclosure of f over x against dense samples
+    fn assert_encloses(f: &Function, x: Interval) {
+        let y = f.eval_interval([x]);
+        for i in 0..=1000 {
+            let t = x.lo + x.width() * (i as f64) / 1000.0;
+            let v: f64 = f.eval([t]);
+            if v.is_finite() {
+                assert!(y.contains(v), "{f} on {x:?}: {v} not in {y:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_enclosures() {
+        let functions = [
+            fn_sin(X),
+            fn_cos(fn_mul(fn_const(3.0), X)),
+            fn_tan(X),
+            fn_exp(fn_neg(fn_powi(X, 2))),
+            fn_log(X),
+            fn_div(fn_const(1.0), fn_sub(X, fn_const(0.5))),
+            fn_powi(fn_sub(X, fn_const(0.3)), 4),
+            fn_powi(X, -3),
+            fn_poly(vec![1.0, -3.0, 0.0, 2.0]),
+            fn_sum(vec![fn_sin(X), fn_prod(vec![X, X, fn_cos(X)])]),
+        ];
+        let boxes = [
+            Interval::new(-0.1, 0.2),
+            Interval::new(1.0, 2.0),
+            Interval::new(-4.0, 7.0),
+            Interval::new(0.6, 0.7),
+        ];
+
+        for f in &functions {
+            for &x in &boxes {
+                assert_encloses(f, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_special_cases() {
+        // the maximum of sin inside the interval
+        let y = fn_sin(X).eval_interval([Interval::new(1.0, 2.0)]);
+        assert_eq!(y.hi, 1.0);
+
+        // pole of tan and zero crossing denominators cover everything
+        assert_eq!(
+            fn_tan(X).eval_interval([Interval::new(1.0, 2.0)]),
+            Interval::ENTIRE
+        );
+        let f = fn_div(fn_const(1.0), X);
+        assert_eq!(
+            f.eval_interval([Interval::new(-1.0, 1.0)]),
+            Interval::ENTIRE
+        );
+
+        // log outside of its domain
+        assert!(fn_log(X)
+            .eval_interval([Interval::new(-2.0, -1.0)])
+            .is_empty());
+        assert!(fn_add(fn_log(X), X)
+            .eval_interval([Interval::new(-2.0, -1.0)])
+            .is_empty());
+
+        // huge exponents widen in constant time and stay rigorous
+        let x = Interval::new(1.0, 1.0 + 1e-12);
+        let y = x.powi(i32::MAX);
+        assert!(y.lo <= 1.0 && y.hi >= (1.0 + 1e-12f64).powi(i32::MAX));
+        assert!(x.powi(i32::MIN).contains(1.0));
+        let y = Interval::new(0.5, 2.0).powi(200);
+        assert!(y.contains(0.5f64.powi(200)) && y.contains(2.0f64.powi(200)));
+    }
+
+    #[test]
+    fn test_isolate_roots() {
+        // (x + 1)(x - 0.5)(x - 2) has three simple roots
+        let f = fn_poly_with_roots(&[-1.0, 0.5, 2.0]);
+        let roots = isolate_roots(&f, (-3.0, 3.0), 1e-6);
+
+        assert_eq!(roots.len(), 3);
+        for (enclosure, r) in roots.iter().zip([-1.0, 0.5, 2.0]) {
+            assert!(enclosure.interval.contains(r));
+            assert!(enclosure.verified);
+        }
+
+        // sin(x) + 0.999 - exp(-1e6 * x^2) only dips below zero in a thin
+        // spike around 0, far narrower than any sampling grid
+        let spike = fn_sub(
+            fn_add(fn_sin(X), fn_const(0.999)),
+            fn_exp(fn_mul(fn_const(-1e6), fn_powi(X, 2))),
+        );
+        let roots = isolate_roots(&spike, (-1.0, 1.0), 1e-6);
+        assert_eq!(roots.len(), 2);
+        assert!(roots.iter().all(|r| r.interval.lo.abs() < 1e-2));
+
+        // a tolerance below the float spacing stops at adjacent floats
+        let roots = isolate_roots(&fn_sub(X, fn_const(0.1)), (0.0, 1.0), 1e-300);
+        assert_eq!(roots.len(), 1);
+        let x = roots[0].interval;
+        assert!(x.contains(0.1) && x.hi <= f64::from_bits(x.lo.to_bits() + 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "tolerance must be positive")]
+    fn test_isolate_roots_tolerance() {
+        isolate_roots(&X, (-1.0, 1.0), 0.0);
+    }
+}
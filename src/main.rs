@@ -1,22 +1,39 @@
 use macroquad::prelude::*;
-use macroquad::ui::{hash, root_ui, widgets, Skin};
+use macroquad::ui::{root_ui, Skin};
+use ndarray::{Array1, Axis};
 
 // -----------------------------------------------------------------------------
 
+mod antiderivative;
 mod approx;
+mod autodiff;
 mod cam;
+mod compile;
+mod dag;
+mod dual;
 mod func;
 mod integration;
+mod interval;
 mod ml;
+mod panel;
+mod parallel;
+mod parametric;
+mod parse;
 mod polynomial;
+mod rational;
+mod roots;
+mod scalar;
+mod simplify;
 mod util;
 
-use approx::compute_legendre_approx;
+use approx::{compute_legendre_approx, mean_squared_error};
 use cam::Camera;
 use func::*;
-use integration::{int_inner_product, IntMethod};
+use integration::IntMethod;
+use panel::{ExpressionPanel, Plot, POLAR_RANGE};
 use polynomial::Polynomial;
-use util::{sample_interval_equidistributed, sample_interval_random, Point};
+use rational::Rational;
+use util::sample_interval_random;
 
 // =============================================================================
 
@@ -30,12 +47,10 @@ async fn main() {
     let mut cam = Camera::default();
 
     // function setup
-    let f = fn_sum(vec![
-        fn_powi(X, 3),
-        fn_neg(fn_exp(X)),
-        fn_div(fn_sin(fn_mul(fn_const(10.0), X)), fn_const(4.0)),
-        fn_const(1.0),
-    ]);
+    let source = "x^3 - exp(x) + sin(10x)/4 + 1";
+    let f: Function = source.parse().unwrap();
+
+    let mut panel = ExpressionPanel::new(&[source]);
 
     let p = compute_legendre_approx(&f, 12, IntMethod::CompositeTrapezoidal(10000));
 
@@ -48,6 +63,9 @@ async fn main() {
     // let mut p = Polynomial::new_random_with_degree(16);
     // let mut coeffs = p.coefficients.clone();
 
+    // where the right button was pressed, while it is held
+    let mut bracket_start: Option<f64> = None;
+
     loop {
         clear_background(WHITE);
 
@@ -56,50 +74,165 @@ async fn main() {
         // update camera aspect ratio to match screen aspect ratio (in case of window rescale)
         cam.set_aspect_ratio((screen_width() / screen_height()) as f64);
 
+        // ignore the mouse while it is over the expression panel
+        let over_ui = root_ui().is_mouse_over(mouse_position().into());
+
         // move camera when screen is dragged
-        if is_mouse_button_down(MouseButton::Left) {
+        if is_mouse_button_down(MouseButton::Left) && !over_ui {
             cam.move_by(mouse_delta_position());
         }
 
         // zoom camera when scrolling
         let y_scroll = mouse_wheel().1;
-        if y_scroll != 0.0 {
+        if y_scroll != 0.0 && !over_ui {
             cam.zoom_by(y_scroll);
         }
 
+        // hold the right button to solve the graphs from where it was pressed
+        if is_mouse_button_pressed(MouseButton::Right) && !over_ui {
+            bracket_start = Some(cam.screen_to_euc(mouse_position()).x);
+        }
+        if !is_mouse_button_down(MouseButton::Right) {
+            bracket_start = None;
+        }
+
+        // toggle drawing expressions as guaranteed enclosures
+        if is_key_pressed(KeyCode::F2) {
+            cam.guaranteed = !cam.guaranteed;
+        }
+
+        // toggle marking the roots of expressions
+        if is_key_pressed(KeyCode::F3) {
+            cam.show_roots = !cam.show_roots;
+        }
+
+        // toggle between the cartesian and the polar grid
+        if is_key_pressed(KeyCode::F4) {
+            cam.polar_grid = !cam.polar_grid;
+        }
+
+        // toggle tracing the graphs at the mouse
+        if is_key_pressed(KeyCode::F5) {
+            cam.show_tangents = !cam.show_tangents;
+        }
+
         // computations --------------------------------------------------------
 
         let interval = (-1.0, 1.0);
 
-        let xs: Vec<ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>> =
-            sample_interval_random(interval, 200)
-                .iter()
-                .map(|x| ndarray::arr1(&[*x]))
-                .collect();
-        let ys: Vec<ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>> = xs
-            .iter()
-            .map(|x| ndarray::arr1(&[f.eval([x[0]])]))
-            .collect();
+        // one sample point per row
+        let xs = Array1::from(sample_interval_random(interval, 200)).insert_axis(Axis(1));
+        let ys = f.eval_batch(&xs.view()).insert_axis(Axis(1));
 
-        nn.train_batch(&xs, &ys, 0.01);
+        nn.train_batch(&xs.view(), &ys.view(), 0.01);
+
+        // how far both approximations are from f on this frame's sample
+        let p_error = mean_squared_error(&p, &f, &xs.view());
+        let nn_error = mean_squared_error(&nn, &f, &xs.view());
 
         // approx::compute_gradient_descent_step(&f, &mut coeffs, (-1.0, 1.0), 1000, 0.1);
         // p.coefficients.clone_from(&coeffs);
 
         // drawing -------------------------------------------------------------
-        cam.draw_grid();
-        cam.draw_function(&f, RED);
+        // domain coloring fills the screen, so only the first one is shown,
+        // underneath everything else
+        let complex = panel.visible_plots().find_map(|(plot, _)| match plot {
+            Plot::Complex(w) => Some(w),
+            _ => None,
+        });
+        if let Some(w) = complex {
+            cam.draw_domain_coloring(w);
+        }
+        if cam.polar_grid {
+            cam.draw_polar_grid();
+        } else {
+            cam.draw_grid();
+        }
+        // the black and white points of the domain coloring, where w is rational
+        if let Some(r) = complex.and_then(Rational::from_function) {
+            let r = r.reduce();
+            let zeros = Polynomial::new(r.num).roots();
+            let poles = Polynomial::new(r.den).roots();
+            cam.draw_zeros_and_poles(&zeros, &poles);
+        }
+        for (plot, color) in panel.visible_plots() {
+            match plot {
+                Plot::Graph(g) if cam.guaranteed => cam.draw_function_enclosure(g, color),
+                Plot::Graph(_) => {}
+                Plot::Implicit(g) => cam.draw_implicit(g, color),
+                Plot::Contours(g) => cam.draw_contour_plot(g, color),
+                Plot::Curve(c) => cam.draw_parametric(c, color),
+                Plot::Polar(r) => cam.draw_polar(r, POLAR_RANGE, color),
+                Plot::Complex(_) => {}
+            }
+        }
+        if !cam.guaranteed {
+            for (g, color) in panel.visible_compiled() {
+                cam.draw_function(g, color);
+            }
+        }
+        if cam.show_roots && cam.guaranteed {
+            for (plot, color) in panel.visible_plots() {
+                if let Plot::Graph(g) = plot {
+                    cam.draw_root_enclosures(g, color);
+                }
+            }
+        } else if cam.show_roots {
+            let options = roots::ScanOptions::default();
+            for (found, color) in panel.visible_roots((cam.left(), cam.right()), &options) {
+                cam.draw_roots(found, color);
+            }
+        }
         cam.draw_function(&p, GREEN);
         cam.draw_function(&nn, PURPLE);
         // cam.draw_function(&p1, GREEN);
         // cam.draw_function(&p2, YELLOW);
+        cam.draw_status(0, &format!("network error {nn_error:.2e}"), PURPLE);
+        cam.draw_status(1, &format!("legendre error {p_error:.2e}"), GREEN);
 
-        // ui ------------------------------------------------------------------
-        root_ui().label(None, "hello megaui");
-        if root_ui().button(None, "Push me") {
-            println!("pushed");
+        if cam.show_tangents && !over_ui {
+            let mouse = cam.screen_to_euc(mouse_position());
+            for (plot, color) in panel.visible_plots() {
+                match plot {
+                    Plot::Graph(g) => {
+                        let (y, gradient) = g.eval_with_gradient([mouse.x]);
+                        cam.draw_tangent(mouse.x, y, gradient[0], color);
+                    }
+                    // the slice through the mouse, as a graph over x
+                    Plot::Contours(g) => cam.draw_function(&g.fix(1, mouse.y), color),
+                    _ => {}
+                }
+            }
+        }
+
+        // the root finding methods side by side: bisection and secant between
+        // where the right button was pressed and the mouse, newton from the mouse
+        if let Some(a) = bracket_start {
+            let b = cam.screen_to_euc(mouse_position()).x;
+            let tol = roots::Tolerances::default();
+            let mut line = 2;
+            for (plot, color) in panel.visible_plots() {
+                let Plot::Graph(g) = plot else {
+                    continue;
+                };
+                let runs = [
+                    ("bisection", roots::bisection(g, (a, b), &tol)),
+                    ("secant", roots::secant(g, (a, b), &tol)),
+                    ("newton", roots::newton(g, b, &tol)),
+                ];
+                for (method, run) in runs {
+                    if run.converged() {
+                        cam.draw_roots(&[run.root], color);
+                    }
+                    cam.draw_status(line, &format!("{method}: {run}"), color);
+                    line += 1;
+                }
+            }
         }
 
+        // ui ------------------------------------------------------------------
+        panel.ui(&mut root_ui(), (cam.left(), cam.right()));
+
         // finish frame --------------------------------------------------------
         next_frame().await
     }
@@ -120,7 +253,7 @@ fn load_skin() -> Skin {
         .font(font_bytes)
         .unwrap()
         .text_color(Color::from_rgba(180, 180, 100, 255))
-        .font_size(40)
+        .font_size(20)
         .build();
 
     let editbox_style = root_ui()
@@ -130,7 +263,7 @@ fn load_skin() -> Skin {
         .unwrap()
         .text_color(Color::from_rgba(120, 120, 120, 255))
         .color_selected(Color::from_rgba(190, 190, 190, 255))
-        .font_size(50)
+        .font_size(20)
         .build();
 
     Skin {
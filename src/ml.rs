@@ -1,8 +1,16 @@
-use ndarray::{s, Array1, Array2};
+use ndarray::{concatenate, Array1, Array2, ArrayView1, ArrayView2, Axis, Slice};
 use rand::Rng;
 
-use crate::EvaluateOne;
+use crate::autodiff::{Gradients, Tape, Var};
+use crate::parallel;
+use crate::{EvaluateMany, EvaluateOne};
 
+/// rows of a training batch recorded on one tape
+///
+/// fixed so the gradients add up in the same order for any number of threads
+const TRAIN_CHUNK_SIZE: usize = 64;
+
+#[allow(dead_code)]
 pub struct NeuralNetwork {
     input_size: usize,
     hidden_size: usize,
@@ -28,94 +36,124 @@ impl NeuralNetwork {
 
     pub fn forward(&self, input: &Array1<f64>) -> Array1<f64> {
         // add a 1 to end for bias trick
-        let aug_input = augment(input);
+        let aug_input = augment(input.view());
 
         let hidden = self.weights_ih.dot(&aug_input);
         let hidden = hidden.map(|x| leaky_relu(*x));
 
         // add a 1 to end for bias trick
-        let aug_hidden = augment(&hidden);
+        let aug_hidden = augment(hidden.view());
 
         let output = self.weights_ho.dot(&aug_hidden);
         // output.map(|x| leaky_relu(*x))
         output
     }
 
+    /// `forward` for every row of `inputs` at once, returns the outputs as rows
+    pub fn forward_batch(&self, inputs: &ArrayView2<f64>) -> Array2<f64> {
+        // bias trick on every row
+        let aug_inputs = augment_rows(inputs);
+        let hidden = aug_inputs.dot(&self.weights_ih.t()).mapv_into(leaky_relu);
+
+        let aug_hidden = augment_rows(&hidden.view());
+        aug_hidden.dot(&self.weights_ho.t())
+    }
+
+    /// one step of gradient descent on the squared error |target - output|^2 / 2
+    /// averaged over the batch, with gradients from a reverse-mode tape
+    ///
+    /// each row of `inputs` is a sample, with the matching row of `targets`.
+    /// the batch is split into chunks of `TRAIN_CHUNK_SIZE` rows with a tape
+    /// each (on the thread pool with the `parallel` feature), and their
+    /// gradients are added in order
     pub fn train_batch(
         &mut self,
-        inputs: &Vec<Array1<f64>>,
-        targets: &Vec<Array1<f64>>,
+        inputs: &ArrayView2<f64>,
+        targets: &ArrayView2<f64>,
         learning_rate: f64,
     ) {
-        let batch_size = inputs.len();
-
-        let mut hidden_outs = Vec::with_capacity(batch_size);
-        let mut output_outs = Vec::with_capacity(batch_size);
-
-        // forward pass
-        for input in inputs {
-            let aug_input = augment(input);
-
-            let hidden = self.weights_ih.dot(&aug_input);
-            let hidden_activated = hidden.map(|x| leaky_relu(*x));
-            hidden_outs.push(hidden_activated.clone());
-
-            let aug_hidden = augment(&hidden_activated);
-            let output = self.weights_ho.dot(&aug_hidden);
-            // let output_activated = output.map(|x| leaky_relu(*x));
-            output_outs.push(output.clone());
+        let batch_size = inputs.nrows();
+        if batch_size == 0 {
+            return;
         }
 
-        // compute error
-        let output_errors: Vec<Array1<f64>> = targets
-            .iter()
-            .zip(output_outs.iter())
-            .map(|(target, output_activated)| target - output_activated)
-            .collect();
-
-        let hidden_errors: Vec<Array1<f64>> = output_errors
-            .iter()
-            .zip(hidden_outs.iter())
-            .map(|(output_error, hidden_output)| {
-                let error = self.weights_ho.t().slice(s![..-1, ..]).dot(output_error);
-                &error * &hidden_output.map(|x| leaky_relu_derivative(*x))
-            })
-            .collect();
-
-        // update weights
-
-        let mut weight_ho_update: Array2<f64> = Array2::zeros(self.weights_ho.raw_dim());
-        let mut weight_ih_update: Array2<f64> = Array2::zeros(self.weights_ih.raw_dim());
-
-        for ((input, hidden_output), (output_error, hidden_error)) in inputs
-            .iter()
-            .zip(hidden_outs.iter())
-            .zip(output_errors.iter().zip(hidden_errors.iter()))
-        {
-            let aug_input = augment(input);
-            let aug_hidden = augment(hidden_output);
-
-            // update weights_ho
-            for (i, delta) in output_error.iter().enumerate() {
-                for (j, &h) in aug_hidden.iter().enumerate() {
-                    weight_ho_update[[i, j]] += delta * h;
-                }
-            }
-
-            // update weights_ih
-            for (i, delta) in hidden_error.iter().enumerate() {
-                for (j, &inp) in aug_input.iter().enumerate() {
-                    weight_ih_update[[i, j]] += delta * inp;
-                }
-            }
+        let chunks = batch_size.div_ceil(TRAIN_CHUNK_SIZE);
+        let chunk_grads = parallel::map_indexed(chunks, |c| {
+            let rows =
+                Slice::from(c * TRAIN_CHUNK_SIZE..((c + 1) * TRAIN_CHUNK_SIZE).min(batch_size));
+            self.loss_gradients(
+                &inputs.slice_axis(Axis(0), rows),
+                &targets.slice_axis(Axis(0), rows),
+            )
+        });
+
+        let mut grad_ih = Array2::zeros(self.weights_ih.raw_dim());
+        let mut grad_ho = Array2::zeros(self.weights_ho.raw_dim());
+        for (ih, ho) in chunk_grads {
+            grad_ih += &ih;
+            grad_ho += &ho;
         }
 
-        // apply averaged updates
-        self.weights_ho += &(weight_ho_update * (learning_rate / batch_size as f64));
-        self.weights_ih += &(weight_ih_update * (learning_rate / batch_size as f64));
+        let step = learning_rate / (batch_size as f64);
+        self.weights_ih.scaled_add(-step, &grad_ih);
+        self.weights_ho.scaled_add(-step, &grad_ho);
+    }
+
+    /// gradients of the summed (not averaged) squared error over the rows,
+    /// with respect to `weights_ih` and `weights_ho`
+    fn loss_gradients(
+        &self,
+        inputs: &ArrayView2<f64>,
+        targets: &ArrayView2<f64>,
+    ) -> (Array2<f64>, Array2<f64>) {
+        let tape = Tape::new();
+        let weights_ih = record_matrix(&tape, &self.weights_ih);
+        let weights_ho = record_matrix(&tape, &self.weights_ho);
+
+        let errors = inputs
+            .rows()
+            .into_iter()
+            .zip(targets.rows())
+            .map(|(input, target)| {
+                // add a 1 to end for bias trick
+                let aug_input: Vec<Var> = tape.vars(augment(input).as_slice().unwrap());
+
+                let hidden = weights_ih.iter().map(|row| {
+                    let h = tape.dot(row, &aug_input);
+                    h.unary(leaky_relu(h.value()), leaky_relu_derivative(h.value()))
+                });
+                let aug_hidden: Vec<Var> = hidden.chain(std::iter::once(tape.var(1.0))).collect();
+
+                let output = weights_ho.iter().map(|row| tape.dot(row, &aug_hidden));
+
+                tape.sum(output.zip(target).map(|(o, &t)| {
+                    let diff = o - t;
+                    diff * diff * 0.5
+                }))
+            });
+        let loss = tape.sum(errors);
+
+        let grads = loss.backward();
+        (
+            gradient_matrix(&weights_ih, &grads),
+            gradient_matrix(&weights_ho, &grads),
+        )
     }
 }
 
+/// records every entry of the matrix on the tape, row by row
+fn record_matrix<'t>(tape: &'t Tape, m: &Array2<f64>) -> Vec<Vec<Var<'t>>> {
+    m.rows()
+        .into_iter()
+        .map(|row| row.iter().map(|&w| tape.var(w)).collect())
+        .collect()
+}
+
+/// dloss/dw for every entry w recorded by `record_matrix`
+fn gradient_matrix(vars: &[Vec<Var>], grads: &Gradients) -> Array2<f64> {
+    Array2::from_shape_fn((vars.len(), vars[0].len()), |(i, j)| grads.wrt(vars[i][j]))
+}
+
 fn leaky_relu(x: f64) -> f64 {
     if x > 0.0 {
         x
@@ -132,19 +170,27 @@ fn leaky_relu_derivative(x: f64) -> f64 {
     }
 }
 
+#[allow(dead_code)]
 fn sigmoid(x: f64) -> f64 {
     1.0 / (1.0 + (-x).exp())
 }
 
+#[allow(dead_code)]
 fn sigmoid_derivative(x: f64) -> f64 {
     let s = sigmoid(x);
     s * (1.0 - s)
 }
 
-fn augment(v: &Array1<f64>) -> Array1<f64> {
+fn augment(v: ArrayView1<f64>) -> Array1<f64> {
     Array1::from_iter(v.iter().cloned().chain(std::iter::once(1.0)))
 }
 
+/// appends a column of ones
+fn augment_rows(m: &ArrayView2<f64>) -> Array2<f64> {
+    let ones = Array2::ones((m.nrows(), 1));
+    concatenate![Axis(1), *m, ones]
+}
+
 impl EvaluateOne for NeuralNetwork {
     fn eval_one(&self, x: f64) -> f64 {
         let input = Array1::from(vec![x]);
@@ -152,3 +198,54 @@ impl EvaluateOne for NeuralNetwork {
         output[0]
     }
 }
+
+impl EvaluateMany for NeuralNetwork {
+    /// the first output for each row of `points`
+    fn eval_batch(&self, points: &ArrayView2<f64>) -> Array1<f64> {
+        self.forward_batch(points).column(0).to_owned()
+    }
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::sample_interval_equidistributed;
+
+    fn mean_squared_error(nn: &NeuralNetwork, xs: &Array2<f64>, ys: &Array2<f64>) -> f64 {
+        let diff = nn.forward_batch(&xs.view()) - ys;
+        diff.mapv(|d| d * d).mean().unwrap()
+    }
+
+    #[test]
+    fn test_forward_batch() {
+        let nn = NeuralNetwork::new(2, 8, 3);
+        let xs = Array2::from_shape_fn((5, 2), |(i, j)| i as f64 - 0.7 * j as f64);
+
+        let batch = nn.forward_batch(&xs.view());
+        for (row, out) in xs.rows().into_iter().zip(batch.rows()) {
+            let single = nn.forward(&row.to_owned());
+            for (a, b) in single.iter().zip(out) {
+                assert!((a - b).abs() < 1e-12);
+            }
+        }
+        assert_eq!(nn.eval_batch(&xs.view()), batch.column(0));
+    }
+
+    #[test]
+    fn test_train_batch() {
+        let xs =
+            Array1::from(sample_interval_equidistributed((-1.0, 1.0), 50)).insert_axis(Axis(1));
+        let ys = xs.mapv(|x| x * x);
+
+        let mut nn = NeuralNetwork::new(1, 16, 1);
+        let before = mean_squared_error(&nn, &xs, &ys);
+        for _ in 0..200 {
+            nn.train_batch(&xs.view(), &ys.view(), 0.05);
+        }
+        let after = mean_squared_error(&nn, &xs, &ys);
+
+        assert!(after < before, "{after} >= {before}");
+    }
+}
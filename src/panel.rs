@@ -0,0 +1,696 @@
+use macroquad::prelude::*;
+use macroquad::ui::{hash, widgets, Ui};
+use std::ops::Range;
+
+// -----------------------------------------------------------------------------
+
+use crate::antiderivative::fn_integrate;
+use crate::compile::CompiledFunction;
+use crate::dag::Dag;
+use crate::func::{fn_pdv, fn_sub, Function};
+use crate::integration::{int_antiderivative, int_error, integrate, IntMethod};
+use crate::parametric::ParametricCurve;
+use crate::parse::{is_reserved, parse_with_functions, ParseError};
+use crate::polynomial::{poly_scale, Polynomial};
+use crate::rational::Rational;
+use crate::roots::{find_roots_with, ScanOptions};
+
+// =============================================================================
+
+/// colors handed out to new rows, clicking a swatch cycles through them
+const PALETTE: [Color; 8] = [
+    RED, BLUE, DARKGREEN, PURPLE, ORANGE, MAGENTA, SKYBLUE, BROWN,
+];
+
+const PANEL_WIDTH: f32 = 420.0;
+const PANEL_HEIGHT: f32 = 360.0;
+const SWATCH_SIZE: f32 = 20.0;
+
+/// parameter range of the curves `(x(t), y(t))`
+const CURVE_PARAMETER_RANGE: (f64, f64) = (0.0, std::f64::consts::TAU);
+
+/// range of theta the polar graphs `r = f(theta)` are drawn over
+pub const POLAR_RANGE: (f64, f64) = (0.0, std::f64::consts::TAU);
+
+/// rule for the measurements under the rows: arc lengths, and the integrals
+/// over the view that are checked against the antiderivative
+const INFO_INT_METHOD: IntMethod = IntMethod::CompositeTrapezoidal(1000);
+
+// Plots =======================================================================
+
+/// what a row draws, decided by the form of its text
+#[derive(Debug, Clone, PartialEq)]
+pub enum Plot {
+    /// `f(x)` or `y = f(x)`: the graph of f
+    Graph(Function),
+    /// `f(x, y) = g(x, y)`: the curve where both sides agree, as the zero set
+    /// of f - g
+    Implicit(Function),
+    /// `f(x, y)`: level sets of f
+    Contours(Function),
+    /// `(x(t), y(t))`: a parametric curve over `CURVE_PARAMETER_RANGE`
+    Curve(ParametricCurve),
+    /// `r = f(theta)`: a polar graph over `POLAR_RANGE`, with theta as x_0
+    Polar(Function),
+    /// `w = f(z)`: domain coloring of a complex function, with z as x_0
+    Complex(Function),
+}
+
+/// the functions of x_0 defined by rows `name(param) = ...`, with their names
+pub type Definitions = [(String, Function)];
+
+impl Plot {
+    /// parses the text of a row, a curve if it is a parenthesized pair, an
+    /// equation if it has a `=` outside of parentheses and a plain expression
+    /// otherwise. the defined functions can be called by name
+    pub fn parse(text: &str, functions: &Definitions) -> Result<Plot, ParseError> {
+        if let Some((x, y)) = split_pair(text) {
+            let x = parse_at(text, x, &["t"], functions)?;
+            let y = parse_at(text, y, &["t"], functions)?;
+            return Ok(Plot::Curve(ParametricCurve::new(
+                x,
+                y,
+                CURVE_PARAMETER_RANGE,
+            )));
+        }
+
+        let Some(eq) = find_top_level(text, '=') else {
+            let f = parse_with_functions(text, &[], functions)?;
+            return Ok(if f.depends_on(1) {
+                Plot::Contours(f)
+            } else {
+                Plot::Graph(f)
+            });
+        };
+
+        let rhs = eq + 1..text.len();
+        if let Some((_, param)) = split_definition(&text[..eq]) {
+            return Ok(Plot::Graph(parse_at(text, rhs, &[param], functions)?));
+        }
+        if text[..eq].trim() == "r" {
+            let r = parse_at(text, rhs, &["theta"], functions)?;
+            return Ok(Plot::Polar(r));
+        }
+        if text[..eq].trim() == "w" {
+            let w = parse_at(text, rhs, &["z"], functions)?;
+            return Ok(Plot::Complex(w));
+        }
+
+        let rhs = parse_at(text, rhs, &[], functions)?;
+        if text[..eq].trim() == "y" && !rhs.depends_on(1) {
+            return Ok(Plot::Graph(rhs));
+        }
+        let lhs = parse_at(text, 0..eq, &[], functions)?;
+        Ok(Plot::Implicit(fn_sub(lhs, rhs)))
+    }
+}
+
+/// byte index of the first `sep` outside of parentheses, none if a `)`
+/// closes more than was opened before it
+fn find_top_level(text: &str, sep: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            c if c == sep && depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// the byte ranges of `a` and `b` if the text is of the form `(a, b)`
+fn split_pair(text: &str) -> Option<(Range<usize>, Range<usize>)> {
+    let trimmed = text.trim();
+    if trimmed.len() < 2 || !trimmed.starts_with('(') || !trimmed.ends_with(')') {
+        return None;
+    }
+    let start = text.len() - text.trim_start().len() + 1;
+    let end = text.trim_end().len() - 1;
+    let comma = start + find_top_level(&text[start..end], ',')?;
+    Some((start..comma, comma + 1..end))
+}
+
+/// the name and parameter of the left-hand side `name(param)` of a function
+/// definition, names that already mean something can't be defined
+fn split_definition(lhs: &str) -> Option<(&str, &str)> {
+    let (name, param) = lhs.trim().strip_suffix(')')?.split_once('(')?;
+    let (name, param) = (name.trim(), param.trim());
+    let is_name = |s: &str| !s.is_empty() && s.chars().all(char::is_alphabetic);
+    (is_name(name) && is_name(param) && !is_reserved(name)).then_some((name, param))
+}
+
+/// parses part of `text` with the given variable names (the default ones if
+/// empty), with error spans relative to the whole of it
+fn parse_at(
+    text: &str,
+    range: Range<usize>,
+    variables: &[&str],
+    functions: &Definitions,
+) -> Result<Function, ParseError> {
+    let start = range.start;
+    parse_with_functions(&text[range], variables, functions).map_err(|err| ParseError {
+        span: err.span.start + start..err.span.end + start,
+        ..err
+    })
+}
+
+// Expression Row ==============================================================
+
+/// one editable line of the panel: the source text and what it parsed into
+pub struct ExpressionRow {
+    id: u64,
+    pub text: String,
+    pub color: Color,
+    pub visible: bool,
+    parsed_text: String,
+    parsed: Result<Plot, ParseError>,
+    /// the function of a graph compiled for drawing
+    compiled: Option<CompiledFunction>,
+    /// the derivative compiled for root finding, built on first use
+    derivative: Option<CompiledFunction>,
+    /// the function of a graph as a polynomial, if it is one, whose roots are
+    /// found by sturm sequences instead
+    polynomial: Option<Polynomial>,
+    /// the roots last found, with the range they were searched in
+    roots: Option<((f64, f64), Vec<f64>)>,
+    /// the last `info`, with the range it was measured over
+    info: Option<((f64, f64), Option<String>)>,
+}
+
+impl ExpressionRow {
+    fn new(id: u64, text: &str, color: Color, functions: &Definitions) -> Self {
+        let mut row = ExpressionRow {
+            id,
+            text: text.to_string(),
+            color,
+            visible: true,
+            parsed_text: text.to_string(),
+            parsed: Plot::parse(text, functions),
+            compiled: None,
+            derivative: None,
+            polynomial: None,
+            roots: None,
+            info: None,
+        };
+        row.compiled = row.function().map(Function::compile);
+        row.polynomial = row.function().and_then(as_polynomial);
+        row
+    }
+
+    /// whether the text was edited since it was last parsed
+    fn edited(&self) -> bool {
+        self.text != self.parsed_text
+    }
+
+    /// parses the text again, with the given functions defined
+    fn parse(&mut self, functions: &Definitions) {
+        self.parsed_text.clone_from(&self.text);
+        self.parsed = Plot::parse(&self.text, functions);
+        self.compiled = self.function().map(Function::compile);
+        self.derivative = None;
+        self.polynomial = self.function().and_then(as_polynomial);
+        self.roots = None;
+        self.info = None;
+    }
+
+    pub fn plot(&self) -> Option<&Plot> {
+        self.parsed.as_ref().ok()
+    }
+
+    /// the function of a graph, other plots have none
+    pub fn function(&self) -> Option<&Function> {
+        match self.plot()? {
+            Plot::Graph(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// the name of the function the row defines, if it is a definition
+    pub fn defined_name(&self) -> Option<&str> {
+        self.plot()?;
+        let eq = find_top_level(&self.parsed_text, '=')?;
+        split_definition(&self.parsed_text[..eq]).map(|(name, _)| name)
+    }
+
+    pub fn compiled(&self) -> Option<&CompiledFunction> {
+        self.compiled.as_ref()
+    }
+
+    /// the converged roots in `range`, searched again only when the range
+    /// differs from the last call (edits clear the cache). every distinct root
+    /// of a polynomial is found, even where it doesn't change sign
+    pub fn roots(&mut self, range: (f64, f64), options: &ScanOptions) -> Option<&[f64]> {
+        let Some(Plot::Graph(f)) = self.parsed.as_ref().ok() else {
+            return None;
+        };
+        let compiled = self.compiled.as_ref()?;
+        if !matches!(&self.roots, Some((cached, _)) if *cached == range) {
+            let roots = if let Some(p) = &self.polynomial {
+                // not where factors cancelled, like x at 0 in x^2 / x
+                p.real_roots(range, options.tolerances.x_tol)
+                    .into_iter()
+                    .filter(|&x| compiled.eval(&[x]).is_finite())
+                    .collect()
+            } else {
+                let df = self.derivative.get_or_insert_with(|| {
+                    let mut dag = Dag::new();
+                    let root = dag.add_function(f);
+                    let df = dag.pdv(root, 0);
+                    CompiledFunction::from_dag(&dag, df)
+                });
+                find_roots_with(compiled, df, range, options)
+                    .iter()
+                    .filter(|r| r.converged())
+                    .map(|r| r.root)
+                    .collect()
+            };
+            self.roots = Some((range, roots));
+        }
+        self.roots.as_ref().map(|(_, roots)| roots.as_slice())
+    }
+
+    /// a measurement to show under the row, if its kind of plot has one: the
+    /// integral of a graph over `range` (the visible one), or the length of a
+    /// curve. measured again only when the range differs from the last call
+    pub fn info(&mut self, range: (f64, f64)) -> Option<&str> {
+        if !matches!(&self.info, Some((cached, _)) if *cached == range) {
+            let info = match self.plot() {
+                Some(Plot::Graph(f)) => Some(integral_info(f, range)),
+                Some(Plot::Curve(curve)) => {
+                    let length = curve.arc_length(INFO_INT_METHOD);
+                    Some(format!("  length {:.4}", length))
+                }
+                _ => None,
+            };
+            self.info = Some((range, info));
+        }
+        self.info.as_ref()?.1.as_deref()
+    }
+
+    /// blank rows are not considered an error, they just draw nothing
+    pub fn error(&self) -> Option<&ParseError> {
+        if self.text.trim().is_empty() {
+            return None;
+        }
+        self.parsed.as_ref().err()
+    }
+
+    /// the simplified derivative of the graph
+    pub fn simplified_derivative(&self) -> Option<Function> {
+        self.function().map(|f| fn_pdv(f, 0).simplify())
+    }
+
+    /// the simplified antiderivative of the graph, if one is found
+    pub fn simplified_antiderivative(&self) -> Option<Function> {
+        fn_integrate(self.function()?, 0).map(|f| f.simplify())
+    }
+
+    fn cycle_color(&mut self) {
+        let i = PALETTE.iter().position(|&c| c == self.color).unwrap_or(0);
+        self.color = PALETTE[(i + 1) % PALETTE.len()];
+    }
+}
+
+/// f as a polynomial of degree at least 1, if it is one (see
+/// `Rational::from_function`)
+fn as_polynomial(f: &Function) -> Option<Polynomial> {
+    let r = Rational::from_function(f)?;
+    match (r.num.len(), r.den.as_slice()) {
+        (2.., [c]) => Some(Polynomial::new(poly_scale(&r.num, 1.0 / c))),
+        _ => None,
+    }
+}
+
+/// the integral of f over the range, exact by the antiderivative with the error
+/// of the numeric rule as a cross-check, or else only the numeric one
+fn integral_info(f: &Function, range: (f64, f64)) -> String {
+    match (
+        int_antiderivative(f, range),
+        int_error(f, range, INFO_INT_METHOD),
+    ) {
+        (Some(exact), Some(error)) => {
+            format!("  integral {exact:.4} (numeric off by {error:.1e})")
+        }
+        _ => {
+            let numeric = integrate(f, range, INFO_INT_METHOD);
+            format!("  integral ~{numeric:.4}")
+        }
+    }
+}
+
+// Expression Panel ============================================================
+
+/// window listing the expressions to plot
+#[derive(Default)]
+pub struct ExpressionPanel {
+    rows: Vec<ExpressionRow>,
+    next_id: u64,
+}
+
+impl ExpressionPanel {
+    pub fn new(expressions: &[&str]) -> Self {
+        let mut panel = ExpressionPanel::default();
+        for text in expressions {
+            panel.push(text);
+        }
+        panel
+    }
+
+    pub fn push(&mut self, text: &str) {
+        let color = PALETTE[self.rows.len() % PALETTE.len()];
+        let row = ExpressionRow::new(self.next_id, text, color, &self.definitions());
+        self.rows.push(row);
+        self.next_id += 1;
+    }
+
+    /// the functions defined by the rows, in order
+    fn definitions(&self) -> Vec<(String, Function)> {
+        self.rows
+            .iter()
+            .filter_map(|row| Some((row.defined_name()?.to_string(), row.function()?.clone())))
+            .collect()
+    }
+
+    /// parses every row again, each with the functions defined above it, so
+    /// that edits to a definition reach the rows calling it
+    fn reparse(&mut self) {
+        let mut functions = Vec::new();
+        for row in self.rows.iter_mut() {
+            row.parse(&functions);
+            if let (Some(name), Some(f)) = (row.defined_name(), row.function()) {
+                functions.push((name.to_string(), f.clone()));
+            }
+        }
+    }
+
+    /// adds a row with the derivative of the graph in row i, unless an
+    /// equivalent graph is already listed
+    pub fn push_derivative(&mut self, i: usize) {
+        if let Some(df) = self.rows[i].simplified_derivative() {
+            self.push_unlisted(&df);
+        }
+    }
+
+    /// adds a row with the antiderivative of the graph in row i, if there is
+    /// one and no equivalent graph is already listed
+    pub fn push_antiderivative(&mut self, i: usize) {
+        if let Some(f) = self.rows[i].simplified_antiderivative() {
+            self.push_unlisted(&f);
+        }
+    }
+
+    fn push_unlisted(&mut self, f: &Function) {
+        let listed = self
+            .rows
+            .iter()
+            .filter_map(ExpressionRow::function)
+            .any(|g| g.is_equivalent(f));
+        if !listed {
+            self.push(&f.to_string());
+        }
+    }
+
+    /// the plots that should be drawn this frame, with their colors
+    pub fn visible_plots(&self) -> impl Iterator<Item = (&Plot, Color)> {
+        self.rows
+            .iter()
+            .filter(|row| row.visible)
+            .filter_map(|row| row.plot().map(|p| (p, row.color)))
+    }
+
+    /// the functions of the visible graphs, compiled for fast sampling
+    pub fn visible_compiled(&self) -> impl Iterator<Item = (&CompiledFunction, Color)> {
+        self.rows
+            .iter()
+            .filter(|row| row.visible)
+            .filter_map(|row| row.compiled().map(|f| (f, row.color)))
+    }
+
+    /// the roots of the visible functions in `range`, with their colors,
+    /// cached per row
+    pub fn visible_roots(
+        &mut self,
+        range: (f64, f64),
+        options: &ScanOptions,
+    ) -> impl Iterator<Item = (&[f64], Color)> {
+        let options = *options;
+        self.rows
+            .iter_mut()
+            .filter(|row| row.visible)
+            .filter_map(move |row| {
+                let color = row.color;
+                row.roots(range, &options).map(|roots| (roots, color))
+            })
+    }
+
+    /// draws the panel and applies any edits made through it, `range` is the
+    /// visible part of the x-axis the rows' integrals are taken over
+    pub fn ui(&mut self, ui: &mut Ui, range: (f64, f64)) {
+        let mut action = None;
+        let mut add = false;
+
+        widgets::Window::new(hash!(), vec2(10.0, 10.0), vec2(PANEL_WIDTH, PANEL_HEIGHT))
+            .label("expressions")
+            .ui(ui, |ui| {
+                for (i, row) in self.rows.iter_mut().enumerate() {
+                    if let Some(a) = draw_row(ui, row, range) {
+                        action = Some((i, a));
+                    }
+                }
+
+                if widgets::Button::new("+").ui(ui) {
+                    add = true;
+                }
+            });
+
+        if self.rows.iter().any(ExpressionRow::edited) {
+            self.reparse();
+        }
+        match action {
+            Some((i, RowAction::Delete)) => {
+                self.rows.remove(i);
+                self.reparse();
+            }
+            Some((i, RowAction::Derive)) => {
+                self.push_derivative(i);
+            }
+            Some((i, RowAction::Integrate)) => {
+                self.push_antiderivative(i);
+            }
+            None => {}
+        }
+        if add {
+            self.push("");
+        }
+    }
+}
+
+/// what the buttons of a row asked the panel to do
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowAction {
+    Delete,
+    /// add a row with the derivative of the row's graph
+    Derive,
+    /// add a row with the antiderivative of the row's graph
+    Integrate,
+}
+
+/// draws a single row: swatch, text box, visibility toggle, derivative and
+/// antiderivative buttons (for graphs), delete button and underneath the error
+/// if the text doesn't parse, or else its `info` over `range`
+///
+/// returns the action of the button that was pressed, if any
+fn draw_row(ui: &mut Ui, row: &mut ExpressionRow, range: (f64, f64)) -> Option<RowAction> {
+    let mut action = None;
+
+    // color swatch, click to cycle
+    let pos = ui.canvas().request_space(vec2(SWATCH_SIZE, SWATCH_SIZE));
+    let swatch = Rect::new(pos.x, pos.y, SWATCH_SIZE, SWATCH_SIZE);
+    let fill = if row.visible { row.color } else { LIGHTGRAY };
+    ui.canvas().rect(swatch, BLACK, fill);
+    if is_mouse_button_pressed(MouseButton::Left) && swatch.contains(mouse_position().into()) {
+        row.cycle_color();
+    }
+
+    ui.same_line(0.0);
+    widgets::InputText::new(hash!("text", row.id))
+        .size(vec2(PANEL_WIDTH - 240.0, 28.0))
+        .ui(ui, &mut row.text);
+
+    ui.same_line(0.0);
+    let toggle = if row.visible { "hide" } else { "show" };
+    if widgets::Button::new(toggle).ui(ui) {
+        row.visible = !row.visible;
+    }
+
+    if row.function().is_some() {
+        ui.same_line(0.0);
+        if widgets::Button::new("d/dx").ui(ui) {
+            action = Some(RowAction::Derive);
+        }
+        ui.same_line(0.0);
+        if widgets::Button::new("int").ui(ui) {
+            action = Some(RowAction::Integrate);
+        }
+    }
+
+    ui.same_line(0.0);
+    if widgets::Button::new("x").ui(ui) {
+        action = Some(RowAction::Delete);
+    }
+
+    if let Some(err) = row.error() {
+        let snippet = row.text.get(err.span.clone()).unwrap_or("");
+        let message = if snippet.is_empty() {
+            format!("  {}", err)
+        } else {
+            format!("  {} (\"{}\")", err, snippet)
+        };
+        widgets::Label::new(message).ui(ui);
+    }
+    if let Some(info) = row.info(range) {
+        widgets::Label::new(info).ui(ui);
+    }
+
+    action
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::func::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn test_plot_kinds() {
+        let graph = |text: &str| Plot::Graph(parse(text).unwrap());
+
+        assert_eq!(Plot::parse("x^2", &[]).unwrap(), graph("x^2"));
+        assert_eq!(Plot::parse("y = sin(x)", &[]).unwrap(), graph("sin(x)"));
+        assert_eq!(
+            Plot::parse("x^2 + y^2 = 1", &[]).unwrap(),
+            Plot::Implicit(fn_sub(parse("x^2 + y^2").unwrap(), fn_const(1.0)))
+        );
+        assert_eq!(
+            Plot::parse("y = x y", &[]).unwrap(),
+            Plot::Implicit(fn_sub(Y, fn_mul(X, Y)))
+        );
+        assert_eq!(
+            Plot::parse("x y", &[]).unwrap(),
+            Plot::Contours(fn_mul(X, Y))
+        );
+
+        let curve = Plot::parse(" (cos(t), sin(2t)) ", &[]).unwrap();
+        let Plot::Curve(curve) = curve else {
+            panic!("{curve:?}");
+        };
+        assert_eq!(curve.x, fn_cos(X));
+        assert_eq!(curve.y, fn_sin(fn_mul(fn_const(2.0), X)));
+
+        assert_eq!(
+            Plot::parse("r = 1 + cos(theta)", &[]).unwrap(),
+            Plot::Polar(fn_add(fn_const(1.0), fn_cos(X)))
+        );
+
+        assert_eq!(
+            Plot::parse("w = 1/z", &[]).unwrap(),
+            Plot::Complex(fn_div(fn_const(1.0), X))
+        );
+
+        // parentheses that don't enclose a pair
+        assert!(Plot::parse("(x + 1)(x - 1)", &[]).is_ok());
+        assert!(Plot::parse("(x), (y)", &[]).is_err());
+
+        // errors point into the whole row
+        let err = Plot::parse("x^2 = (y", &[]).unwrap_err();
+        assert_eq!(err.span, 6..7);
+        let err = Plot::parse("(t, x)", &[]).unwrap_err();
+        assert_eq!(err.span, 4..5);
+    }
+
+    #[test]
+    fn test_derivative_row() {
+        let mut panel = ExpressionPanel::new(&["x^2 sin(x)", "x^2 + y^2 = 1"]);
+        panel.push_derivative(0);
+        assert_eq!(panel.rows.len(), 3);
+
+        let text = &panel.rows[2].text;
+        let Plot::Graph(df) = Plot::parse(text, &[]).unwrap() else {
+            panic!("{text}");
+        };
+        for x in [-1.5, 0.3, 2.0f64] {
+            let expected = 2.0 * x * x.sin() + x * x * x.cos();
+            assert!((df.eval([x]) - expected).abs() < 1e-12, "{text}");
+        }
+
+        // the same derivative isn't listed twice, and only graphs have one
+        panel.push_derivative(0);
+        panel.push_derivative(1);
+        assert_eq!(panel.rows.len(), 3);
+    }
+
+    #[test]
+    fn test_polynomial_roots() {
+        let options = ScanOptions::default();
+        // a double root, which a sign-change scan would miss, and a hole
+        let mut panel = ExpressionPanel::new(&["(x - 1)^2 (x + 2)", "x^2 / x", "sin(x)"]);
+        let roots = panel.rows[0].roots((-5.0, 5.0), &options).unwrap();
+        assert_eq!(roots.len(), 2);
+        assert!((roots[0] + 2.0).abs() < 1e-9 && (roots[1] - 1.0).abs() < 1e-9);
+        assert_eq!(panel.rows[1].roots((-5.0, 5.0), &options), Some(&[][..]));
+        assert!(panel.rows[2].polynomial.is_none());
+        assert_eq!(panel.rows[2].roots((-1.0, 4.0), &options).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_antiderivative_row() {
+        let mut panel = ExpressionPanel::new(&["x cos(x)", "(t, t)"]);
+        panel.push_antiderivative(0);
+        panel.push_antiderivative(1);
+        assert_eq!(panel.rows.len(), 3);
+
+        // x sin(x) + cos(x), up to a constant
+        let f = panel.rows[2].function().unwrap();
+        let area = |x: f64| x * x.sin() + x.cos();
+        let (a, b) = (-0.5, 2.0);
+        assert!((f.eval([b]) - f.eval([a]) - (area(b) - area(a))).abs() < 1e-12);
+
+        let info = panel.rows[0].info((a, b)).unwrap();
+        assert!(
+            info.starts_with(&format!("  integral {:.4} ", area(b) - area(a))),
+            "{info}"
+        );
+        assert!(panel.rows[1].info((a, b)).unwrap().starts_with("  length"));
+    }
+
+    #[test]
+    fn test_definitions() {
+        let mut panel = ExpressionPanel::new(&["f(t) = t^2 + 1", "f(2x)", "g(x) = f(x) - x"]);
+        assert_eq!(panel.rows[0].defined_name(), Some("f"));
+        assert_eq!(panel.rows[1].defined_name(), None);
+        assert_eq!(panel.rows[1].function().unwrap().eval([3.0]), 37.0);
+        assert_eq!(panel.rows[2].function().unwrap().eval([3.0]), 7.0);
+
+        // functions can't be used above their definition, and built-in ones
+        // can't be redefined
+        panel.push("h(x)");
+        panel.push("h(x) = 2x");
+        panel.push("sin(x) = x");
+        assert!(panel.rows[3].error().is_some());
+        assert_eq!(
+            panel.rows[5].plot(),
+            Some(&Plot::Implicit(fn_sub(fn_sin(X), X)))
+        );
+
+        // edits reach the rows calling the function
+        panel.rows[0].text = "f(t) = t".to_string();
+        panel.reparse();
+        assert_eq!(panel.rows[1].function().unwrap().eval([3.0]), 6.0);
+        assert_eq!(panel.rows[2].function().unwrap().eval([3.0]), 0.0);
+    }
+}
@@ -0,0 +1,139 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+// -----------------------------------------------------------------------------
+
+use crate::compile::CompiledFunction;
+use crate::func::Function;
+use crate::ml::NeuralNetwork;
+use crate::polynomial::Polynomial;
+
+// =============================================================================
+
+/// number of terms summed by one task
+///
+/// fixed rather than derived from the number of threads, so the order of
+/// every floating point operation (and thus the result) is the same no
+/// matter how many threads run the tasks, or whether the `parallel` feature
+/// is enabled at all
+pub const CHUNK_SIZE: usize = 1024;
+
+/// computes [f(0), ..., f(n - 1)], on the thread pool with the `parallel`
+/// feature
+pub fn map_indexed<T, F>(n: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        (0..n).into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (0..n).map(f).collect()
+    }
+}
+
+/// computes term(0) + ... + term(n - 1)
+///
+/// the terms are split into chunks of `CHUNK_SIZE`, each summed with
+/// compensated summation (as its own task with the `parallel` feature), and
+/// the chunk sums are added pairwise
+pub fn sum_indexed<F>(n: usize, term: F) -> f64
+where
+    F: Fn(usize) -> f64 + Sync + Send,
+{
+    let chunks = n.div_ceil(CHUNK_SIZE);
+    let partial_sums = map_indexed(chunks, |c| {
+        let end = ((c + 1) * CHUNK_SIZE).min(n);
+        compensated_sum((c * CHUNK_SIZE..end).map(&term))
+    });
+    pairwise_sum(&partial_sums)
+}
+
+/// sums the values with Neumaier's variant of Kahan summation, which
+/// carries the rounding error of each addition along, so the error doesn't
+/// grow with the number of terms
+pub fn compensated_sum<I: IntoIterator<Item = f64>>(values: I) -> f64 {
+    let mut sum = 0.0;
+    let mut compensation = 0.0;
+    for x in values {
+        let t = sum + x;
+        if f64::abs(sum) >= f64::abs(x) {
+            compensation += (sum - t) + x;
+        } else {
+            compensation += (x - t) + sum;
+        }
+        sum = t;
+    }
+    sum + compensation
+}
+
+/// sums the values by recursively adding the sums of both halves, the error
+/// grows only logarithmically with the number of values
+pub fn pairwise_sum(values: &[f64]) -> f64 {
+    match values {
+        [] => 0.0,
+        [x] => *x,
+        _ => {
+            let (left, right) = values.split_at(values.len() / 2);
+            pairwise_sum(left) + pairwise_sum(right)
+        }
+    }
+}
+
+// thread safety ---------------------------------------------------------------
+
+// everything evaluated inside `map_indexed` is shared between worker threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Function>();
+    assert_send_sync::<CompiledFunction>();
+    assert_send_sync::<Polynomial>();
+    assert_send_sync::<NeuralNetwork>();
+};
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sums() {
+        // 1 + 10^6 * 1e-16: the small terms vanish one at a time in a naive sum
+        let values: Vec<f64> = std::iter::once(1.0)
+            .chain(std::iter::repeat_n(1e-16, 1_000_000))
+            .collect();
+        let exact = 1.0 + 1e-10;
+
+        let naive: f64 = values.iter().sum();
+        assert_eq!(naive, 1.0);
+        assert!((compensated_sum(values.iter().copied()) - exact).abs() < 1e-15);
+        assert!((sum_indexed(values.len(), |i| values[i]) - exact).abs() < 1e-15);
+
+        assert_eq!(pairwise_sum(&[]), 0.0);
+        assert_eq!(pairwise_sum(&[1.0, 2.0, 3.0]), 6.0);
+        assert_eq!(map_indexed(4, |i| i * i), vec![0, 1, 4, 9]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_deterministic_across_thread_counts() {
+        let term = |i: usize| ((i as f64) * 0.37).sin() / (1.0 + i as f64);
+
+        let sums: Vec<f64> = [1, 2, 3, 8]
+            .iter()
+            .map(|&threads| {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+                pool.install(|| sum_indexed(100_000, term))
+            })
+            .collect();
+
+        assert!(sums.iter().all(|s| s.to_bits() == sums[0].to_bits()));
+    }
+}
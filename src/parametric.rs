@@ -0,0 +1,161 @@
+use crate::{
+    cam::Viewport,
+    func::*,
+    integration::{integrate, IntMethod},
+    util::Point,
+};
+
+// =============================================================================
+
+/// a curve t -> (x(t), y(t)) in the plane, for t in `t_range`
+///
+/// the parameter t is the variable x_0 of both component functions
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParametricCurve {
+    pub x: Function,
+    pub y: Function,
+    pub t_range: (f64, f64),
+}
+
+impl ParametricCurve {
+    pub fn new(x: Function, y: Function, t_range: (f64, f64)) -> Self {
+        ParametricCurve { x, y, t_range }
+    }
+
+    pub fn eval(&self, t: f64) -> Point {
+        Point::new(self.x.eval([t]), self.y.eval([t]))
+    }
+
+    /// samples the curve over `t_range` in screen coordinates of the view,
+    /// refining in t where it bends on screen
+    pub fn sample(&self, view: &Viewport) -> Vec<Vec<(f32, f32)>> {
+        view.sample(|t| self.eval(t), self.t_range)
+    }
+
+    /// symbolically computes the tangent vector (dx/dt, dy/dt)
+    pub fn tangent(&self) -> (Function, Function) {
+        (fn_pdv(&self.x, 0), fn_pdv(&self.y, 0))
+    }
+
+    /// computes the length of the curve over `t_range` by numerically
+    /// integrating the speed |(dx/dt, dy/dt)|
+    pub fn arc_length(&self, method: IntMethod) -> f64 {
+        let (dx, dy) = self.tangent();
+        integrate(&Speed { dx, dy }, self.t_range, method)
+    }
+}
+
+/// the speed sqrt(x'(t)^2 + y'(t)^2) along a curve, which has no `Function`
+/// representation since there is no square root variant
+struct Speed {
+    dx: Function,
+    dy: Function,
+}
+
+impl EvaluateOne for Speed {
+    fn eval_one(&self, t: f64) -> f64 {
+        self.dx.eval([t]).hypot(self.dy.eval([t]))
+    }
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::{PI, TAU};
+
+    use super::*;
+
+    #[test]
+    fn test_tangent() {
+        // unit circle, tangent is (-sin t, cos t)
+        let circle = ParametricCurve::new(fn_cos(X), fn_sin(X), (0.0, TAU));
+        let (dx, dy) = circle.tangent();
+
+        for t in [0.0f64, 0.5, 2.0, 4.0] {
+            assert!((dx.eval([t]) + t.sin()).abs() < 1e-12);
+            assert!((dy.eval([t]) - t.cos()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_arc_length() {
+        let method = IntMethod::CompositeTrapezoidal(1000);
+
+        // circle of radius 2
+        let circle = ParametricCurve::new(
+            fn_mul(fn_const(2.0), fn_cos(X)),
+            fn_mul(fn_const(2.0), fn_sin(X)),
+            (0.0, TAU),
+        );
+        assert!((circle.arc_length(method) - 4.0 * PI).abs() < 1e-9);
+
+        // straight segment from (0, 0) to (3, 4)
+        let segment = ParametricCurve::new(
+            fn_mul(fn_const(3.0), X),
+            fn_mul(fn_const(4.0), X),
+            (0.0, 1.0),
+        );
+        assert!((segment.arc_length(method) - 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sample() {
+        use crate::cam::PIXEL_TOLERANCE;
+
+        // [-2.5, 2.5]^2 at 1000 pixels per unit
+        let view = Viewport {
+            left: -2.5,
+            top: 2.5,
+            width: 5.0,
+            height: 5.0,
+            screen_width: 5000.0,
+            screen_height: 5000.0,
+        };
+
+        // a straight segment is never refined
+        let segment = ParametricCurve::new(X, fn_mul(fn_const(2.0), X), (-1.0, 1.0));
+        let samples = segment.sample(&view);
+        assert_eq!(samples.len(), 1);
+        let initial = samples[0].len();
+
+        // on a circle every chord stays within the tolerance, which takes
+        // more samples than the initial grid
+        let circle = ParametricCurve::new(
+            fn_mul(fn_const(2.0), fn_cos(X)),
+            fn_mul(fn_const(2.0), fn_sin(X)),
+            (0.0, TAU),
+        );
+        let samples = circle.sample(&view);
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0].len() > initial);
+
+        let center = view.euc_to_screen(Point::new(0.0, 0.0));
+        let radius = view.euc_to_screen(Point::new(2.0, 0.0)).0 - center.0;
+        for chord in samples[0].windows(2) {
+            let mid = (
+                (chord[0].0 + chord[1].0) / 2.0,
+                (chord[0].1 + chord[1].1) / 2.0,
+            );
+            let sagitta = radius - (mid.0 - center.0).hypot(mid.1 - center.1);
+            assert!(sagitta <= PIXEL_TOLERANCE, "{sagitta}");
+        }
+
+        // a narrow bump on a flat line: samples pile up on the bump
+        let bump = ParametricCurve::new(
+            X,
+            fn_exp(fn_mul(fn_const(-50.0), fn_powi(X, 2))),
+            (-2.0, 2.0),
+        );
+        let samples = bump.sample(&view).concat();
+        let count = |inside: fn(f64) -> bool| {
+            samples
+                .iter()
+                .filter(|p| inside((p.0 - center.0) as f64 / 1000.0))
+                .count()
+        };
+        let middle = count(|x| x.abs() < 0.5);
+        let outer = count(|x| x.abs() > 1.5);
+        assert!(middle > 3 * outer, "{middle} vs {outer}");
+    }
+}
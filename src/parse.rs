@@ -0,0 +1,541 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+// -----------------------------------------------------------------------------
+
+use crate::func::*;
+
+// Errors ======================================================================
+
+/// error produced when parsing an expression fails
+///
+/// `span` is the byte range of the offending input, so a ui can underline it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedChar(char),
+    InvalidNumber,
+    UnknownIdentifier(String),
+    ExpectedExpression,
+    ExpectedOpenParen,
+    UnclosedParen,
+    UnmatchedParen,
+    InvalidExponent,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind, span: Range<usize>) -> Self {
+        ParseError { kind, span }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            ParseErrorKind::InvalidNumber => write!(f, "invalid number"),
+            ParseErrorKind::UnknownIdentifier(s) => write!(f, "unknown identifier '{}'", s),
+            ParseErrorKind::ExpectedExpression => write!(f, "expected an expression"),
+            ParseErrorKind::ExpectedOpenParen => write!(f, "expected '(' after function name"),
+            ParseErrorKind::UnclosedParen => write!(f, "unclosed '('"),
+            ParseErrorKind::UnmatchedParen => write!(f, "unmatched ')'"),
+            ParseErrorKind::InvalidExponent => {
+                write!(f, "exponent of a negative base must be an integer")
+            }
+        }?;
+        write!(f, " at {}..{}", self.span.start, self.span.end)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Tokens ======================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LParen,
+    RParen,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    span: Range<usize>,
+}
+
+/// splits the input into tokens, each tagged with its byte range
+///
+/// identifiers are runs of letters, optionally followed by `_` and digits, so
+/// `x_12` is a single token
+fn tokenize(input: &str) -> Result<Vec<Spanned>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let token = match c {
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '^' => Token::Caret,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut end = start;
+                while let Some(&(i, d)) = chars.peek() {
+                    if d.is_ascii_digit() || d == '.' {
+                        end = i + d.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                // exponent: `e` or `E`, an optional sign and at least one
+                // digit. otherwise the `e` is left for the constant, so `2e`
+                // still means 2 * e
+                let mut ahead = chars.clone();
+                if let Some((_, 'e' | 'E')) = ahead.next() {
+                    if let Some(&(_, '+' | '-')) = ahead.peek() {
+                        ahead.next();
+                    }
+                    if matches!(ahead.peek(), Some(&(_, d)) if d.is_ascii_digit()) {
+                        while let Some(&(i, d)) = ahead.peek() {
+                            if d.is_ascii_digit() {
+                                end = i + 1;
+                                ahead.next();
+                            } else {
+                                break;
+                            }
+                        }
+                        chars = ahead;
+                    }
+                }
+                let text = &input[start..end];
+                let value = text
+                    .parse()
+                    .map_err(|_| ParseError::new(ParseErrorKind::InvalidNumber, start..end))?;
+                tokens.push(Spanned {
+                    token: Token::Num(value),
+                    span: start..end,
+                });
+                continue;
+            }
+            c if c.is_alphabetic() => {
+                let mut end = start;
+                while let Some(&(i, d)) = chars.peek() {
+                    if d.is_alphabetic() {
+                        end = i + d.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                // subscript: `_` followed by digits
+                if let Some(&(i, '_')) = chars.peek() {
+                    let mut sub_end = i + 1;
+                    chars.next();
+                    while let Some(&(j, d)) = chars.peek() {
+                        if d.is_ascii_digit() {
+                            sub_end = j + 1;
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    end = sub_end;
+                }
+                tokens.push(Spanned {
+                    token: Token::Ident(input[start..end].to_string()),
+                    span: start..end,
+                });
+                continue;
+            }
+            c => {
+                let span = start..start + c.len_utf8();
+                return Err(ParseError::new(ParseErrorKind::UnexpectedChar(c), span));
+            }
+        };
+
+        chars.next();
+        tokens.push(Spanned {
+            token,
+            span: start..start + c.len_utf8(),
+        });
+    }
+
+    tokens.push(Spanned {
+        token: Token::End,
+        span: input.len()..input.len(),
+    });
+
+    Ok(tokens)
+}
+
+// Parser ======================================================================
+
+/// recursive descent parser over the token stream
+///
+/// grammar, from lowest to highest precedence:
+/// - expr    := term (('+' | '-') term)*
+/// - term    := unary (('*' | '/')? unary)*     (juxtaposition is multiplication)
+/// - unary   := ('-' | '+') unary | power
+/// - power   := primary ('^' unary)?            (right associative)
+/// - primary := number | constant | variable | function '(' expr ')' | '(' expr ')'
+struct Parser<'a> {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    /// names standing for x_0, x_1, ... in place of `x`, `y`, `z`, if any
+    variables: &'a [&'a str],
+    /// functions of x_0 defined elsewhere, applied by composition
+    functions: &'a [(String, Function)],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Spanned {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Spanned {
+        let t = self.tokens[self.pos].clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        t
+    }
+
+    fn parse_expr(&mut self) -> Result<Function, ParseError> {
+        let mut lhs = self.parse_term()?;
+        loop {
+            match self.peek().token {
+                Token::Plus => {
+                    self.next();
+                    lhs = fn_add(lhs, self.parse_term()?);
+                }
+                Token::Minus => {
+                    self.next();
+                    lhs = fn_sub(lhs, self.parse_term()?);
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Function, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            match self.peek().token {
+                Token::Star => {
+                    self.next();
+                    lhs = fn_mul(lhs, self.parse_unary()?);
+                }
+                Token::Slash => {
+                    self.next();
+                    lhs = fn_div(lhs, self.parse_unary()?);
+                }
+                // implicit multiplication, e.g. `10x`, `2sin(x)`, `(x+1)(x-1)`
+                Token::Num(_) | Token::Ident(_) | Token::LParen => {
+                    lhs = fn_mul(lhs, self.parse_power()?);
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Function, ParseError> {
+        match self.peek().token {
+            Token::Minus => {
+                self.next();
+                Ok(fn_neg(self.parse_unary()?))
+            }
+            Token::Plus => {
+                self.next();
+                self.parse_unary()
+            }
+            _ => self.parse_power(),
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<Function, ParseError> {
+        let base = self.parse_primary()?;
+
+        if self.peek().token != Token::Caret {
+            return Ok(base);
+        }
+        let caret = self.next().span;
+        let exp_start = self.peek().span.start;
+        let exponent = self.parse_unary()?;
+        let exp_span = exp_start..self.tokens[self.pos.saturating_sub(1)].span.end;
+
+        match exponent {
+            // integer exponents map onto the PowI variant
+            Function::Const(n) if n.fract() == 0.0 && n.abs() <= i32::MAX as f64 => {
+                Ok(fn_powi(base, n as i32))
+            }
+            exponent => match base {
+                // a^b := exp(b*log(a)), only defined for a > 0
+                Function::Const(c) if c == std::f64::consts::E => Ok(fn_exp(exponent)),
+                Function::Const(c) if c <= 0.0 => Err(ParseError::new(
+                    ParseErrorKind::InvalidExponent,
+                    caret.start..exp_span.end,
+                )),
+                base => Ok(fn_exp(fn_mul(exponent, fn_log(base)))),
+            },
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Function, ParseError> {
+        let Spanned { token, span } = self.next();
+        match token {
+            Token::Num(c) => Ok(fn_const(c)),
+            Token::Ident(name) => self.parse_ident(name, span),
+            Token::LParen => {
+                let inner = self.parse_expr()?;
+                self.expect_close(span)?;
+                Ok(inner)
+            }
+            Token::RParen => Err(ParseError::new(ParseErrorKind::UnmatchedParen, span)),
+            _ => Err(ParseError::new(ParseErrorKind::ExpectedExpression, span)),
+        }
+    }
+
+    fn parse_ident(&mut self, name: String, span: Range<usize>) -> Result<Function, ParseError> {
+        let variable = if self.variables.is_empty() {
+            lookup_variable(&name)
+        } else {
+            self.variables.iter().position(|v| *v == name).map(fn_var)
+        };
+        if let Some(f) = variable.or_else(|| lookup_constant(&name)) {
+            return Ok(f);
+        }
+
+        if let Some((_, f)) = self.functions.iter().rev().find(|(n, _)| *n == name) {
+            let arg = self.parse_argument()?;
+            return Ok(f.compose(&arg));
+        }
+
+        let Some(apply) = lookup_function(&name) else {
+            return Err(ParseError::new(
+                ParseErrorKind::UnknownIdentifier(name),
+                span,
+            ));
+        };
+        let arg = self.parse_argument()?;
+
+        Ok(apply(arg))
+    }
+
+    /// the parenthesized argument of a function
+    fn parse_argument(&mut self) -> Result<Function, ParseError> {
+        let open = self.next();
+        if open.token != Token::LParen {
+            return Err(ParseError::new(
+                ParseErrorKind::ExpectedOpenParen,
+                open.span,
+            ));
+        }
+        let arg = self.parse_expr()?;
+        self.expect_close(open.span)?;
+        Ok(arg)
+    }
+
+    fn expect_close(&mut self, open: Range<usize>) -> Result<(), ParseError> {
+        match self.peek().token {
+            Token::RParen => {
+                self.next();
+                Ok(())
+            }
+            Token::End => Err(ParseError::new(ParseErrorKind::UnclosedParen, open)),
+            _ => Err(ParseError::new(
+                ParseErrorKind::ExpectedExpression,
+                self.peek().span.clone(),
+            )),
+        }
+    }
+}
+
+/// `x`, `y`, `z` are x_0, x_1, x_2, and `x_n` is the n-th variable
+fn lookup_variable(name: &str) -> Option<Function> {
+    match name {
+        "x" => Some(X),
+        "y" => Some(Y),
+        "z" => Some(Z),
+        _ => name.strip_prefix("x_")?.parse().ok().map(fn_var),
+    }
+}
+
+fn lookup_function(name: &str) -> Option<fn(Function) -> Function> {
+    match name {
+        "sin" => Some(fn_sin),
+        "cos" => Some(fn_cos),
+        "tan" => Some(fn_tan),
+        "exp" => Some(fn_exp),
+        "log" | "ln" => Some(fn_log),
+        _ => None,
+    }
+}
+
+/// whether `name` already means a variable, constant or built-in function
+pub fn is_reserved(name: &str) -> bool {
+    lookup_variable(name).is_some()
+        || lookup_constant(name).is_some()
+        || lookup_function(name).is_some()
+}
+
+fn lookup_constant(name: &str) -> Option<Function> {
+    match name {
+        "pi" | "π" => Some(fn_const(std::f64::consts::PI)),
+        "tau" | "τ" => Some(fn_const(std::f64::consts::TAU)),
+        "e" => Some(fn_const(std::f64::consts::E)),
+        _ => None,
+    }
+}
+
+// entry point -----------------------------------------------------------------
+
+/// parses an expression like `x^3 - exp(x) + sin(10x)/4` into a `Function`
+pub fn parse(input: &str) -> Result<Function, ParseError> {
+    parse_with_variables(input, &[])
+}
+
+/// like `parse`, but with the given names for the variables x_0, x_1, ...,
+/// e.g. `["t"]` for the parameter of a curve. `x`, `y` and `z` are then only
+/// variables if they are among the names
+pub fn parse_with_variables(input: &str, variables: &[&str]) -> Result<Function, ParseError> {
+    parse_with_functions(input, variables, &[])
+}
+
+/// like `parse_with_variables`, where a call `name(arg)` of one of the named
+/// functions is the function composed with arg
+pub fn parse_with_functions(
+    input: &str,
+    variables: &[&str],
+    functions: &[(String, Function)],
+) -> Result<Function, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        variables,
+        functions,
+    };
+
+    let f = parser.parse_expr()?;
+
+    let Spanned { token, span } = parser.next();
+    match token {
+        Token::End => Ok(f),
+        Token::RParen => Err(ParseError::new(ParseErrorKind::UnmatchedParen, span)),
+        _ => Err(ParseError::new(ParseErrorKind::ExpectedExpression, span)),
+    }
+}
+
+impl FromStr for Function {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_eval(input: &str, args: &[f64], expected: f64) {
+        let f: Function = input.parse().unwrap();
+        let y = f.eval(args);
+        assert!(
+            (y - expected).abs() < 1e-9,
+            "{input} at {args:?}: {y} != {expected}"
+        );
+    }
+
+    #[test]
+    fn test_parse_eval() {
+        let x: f64 = 0.7;
+
+        assert_eval(
+            "x^3 - exp(x) + sin(10x)/4",
+            &[x],
+            x.powi(3) - x.exp() + (10.0 * x).sin() / 4.0,
+        );
+        assert_eval("1 + 2*3^2", &[], 19.0);
+        assert_eval("-x^2", &[x], -x * x);
+        assert_eval("2^-1", &[], 0.5);
+        assert_eval("2^3^2", &[], 512.0);
+        assert_eval("(x+1)(x-1)", &[x], x * x - 1.0);
+        assert_eval("2pi x", &[x], std::f64::consts::TAU * x);
+        assert_eval("e^x", &[x], x.exp());
+        assert_eval("x^0.5", &[x], x.sqrt());
+        assert_eval("x y + z", &[1.0, 2.0, 3.0], 5.0);
+        assert_eval("x_3 - x_0", &[1.0, 2.0, 3.0, 4.0], 3.0);
+        assert_eval("log(cos(x)) + tan(x)", &[x], x.cos().ln() + x.tan());
+        assert_eval("1e-3", &[], 1e-3);
+        assert_eval("2.5E2", &[], 250.0);
+        assert_eval("3e+1x", &[x], 30.0 * x);
+        assert_eval("2e", &[], 2.0 * std::f64::consts::E);
+        assert_eval("2e-x", &[x], 2.0 * std::f64::consts::E - x);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("x + ", ParseErrorKind::ExpectedExpression, 4..4),
+            ("sin(x", ParseErrorKind::UnclosedParen, 3..4),
+            ("x)", ParseErrorKind::UnmatchedParen, 1..2),
+            ("2 $ x", ParseErrorKind::UnexpectedChar('$'), 2..3),
+            (
+                "foo(x)",
+                ParseErrorKind::UnknownIdentifier("foo".into()),
+                0..3,
+            ),
+            ("sin x", ParseErrorKind::ExpectedOpenParen, 4..5),
+            ("1.2.3", ParseErrorKind::InvalidNumber, 0..5),
+            ("(-2)^x", ParseErrorKind::InvalidExponent, 4..6),
+        ];
+
+        for (input, kind, span) in cases {
+            let err = input.parse::<Function>().unwrap_err();
+            assert_eq!(err, ParseError { kind, span }, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_with_variables() {
+        let f = parse_with_variables("cos(t) + s t", &["t", "s"]).unwrap();
+        assert_eq!(f.eval([2.0, 3.0]), 2f64.cos() + 6.0);
+
+        let err = parse_with_variables("x t", &["t"]).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnknownIdentifier("x".into()));
+    }
+
+    #[test]
+    fn test_parse_with_functions() {
+        let functions = [("f".to_string(), fn_powi(X, 2)), ("sq".to_string(), X)];
+        let f = parse_with_functions("f(2x) + sq(f(y))", &[], &functions).unwrap();
+        assert_eq!(f.eval([3.0, 2.0]), 36.0 + 4.0);
+
+        let err = parse_with_functions("f x", &[], &functions).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::ExpectedOpenParen);
+    }
+}
@@ -1,10 +1,15 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
 use std::vec;
 
+use ndarray::{Array1, ArrayView2};
+use num_complex::Complex64;
 use rand::prelude::*;
 
 // -----------------------------------------------------------------------------
 
-use crate::{func::*, util::factorial};
+use crate::{func::*, scalar::Scalar, util::factorial};
 
 // =============================================================================
 
@@ -14,12 +19,14 @@ pub struct Polynomial {
 }
 
 impl Polynomial {
+    #[allow(dead_code)]
     pub fn new_with_coefficients(coefficients: &[f64]) -> Self {
         Polynomial {
             coefficients: coefficients.to_vec(),
         }
     }
 
+    #[allow(dead_code)]
     pub fn new_random_with_degree(degree: usize) -> Self {
         let mut coefficients = vec![0.0; degree + 1];
 
@@ -31,10 +38,93 @@ impl Polynomial {
         Polynomial::new_with_coefficients(&coefficients)
     }
 
+    pub fn zero() -> Self {
+        Polynomial::default()
+    }
+
+    /// the polynomial with coefficients[k] as the coefficient of x^k, without
+    /// highest coefficients that are 0
+    pub fn new(coefficients: Vec<f64>) -> Self {
+        Polynomial { coefficients }.trimmed()
+    }
+
+    /// highest power with a nonzero coefficient, 0 for the zero polynomial
+    #[allow(dead_code)]
     pub fn degree(&self) -> usize {
-        self.coefficients.len() - 1
+        self.coefficients
+            .iter()
+            .rposition(|&c| c != 0.0)
+            .unwrap_or(0)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.iter().all(|&c| c == 0.0)
+    }
+
+    /// drops the highest coefficients that are 0
+    pub fn trim(&mut self) {
+        self.coefficients = poly_trim(&self.coefficients);
+    }
+
+    pub fn trimmed(mut self) -> Self {
+        self.trim();
+        self
+    }
+
+    /// evaluates at x using horner's method
+    pub fn eval<S: Scalar>(&self, x: S) -> S {
+        poly_eval(&self.coefficients, x)
+    }
+
+    /// polynomial long division: returns (q, r) with self = q*divisor + r and
+    /// deg r < deg divisor
+    ///
+    /// panics if divisor is the zero polynomial
+    pub fn div_rem(&self, divisor: &Polynomial) -> (Polynomial, Polynomial) {
+        let (q, r) = poly_div_rem(&self.coefficients, &divisor.coefficients);
+        (Polynomial::new(q), Polynomial::new(r))
+    }
+
+    /// returns the composition (p∘q)(x) := p(q(x)), by horner's method
+    pub fn compose(&self, inner: &Polynomial) -> Polynomial {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(Polynomial::zero(), |acc, &c| {
+                acc * inner.clone() + Polynomial::new(vec![c])
+            })
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+        Polynomial::new(poly_derivative(&self.coefficients))
+    }
+
+    /// the antiderivative that is 0 at x = 0
+    pub fn antiderivative(&self) -> Polynomial {
+        // int c_k*x^k = c_k/(k+1) * x^(k+1)
+        let shifted = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .map(|(k, c)| c / (k + 1) as f64);
+        Polynomial::new(std::iter::once(0.0).chain(shifted).collect())
+    }
+
+    /// the `Poly` variant, or a constant for degree 0
+    pub fn to_function(&self) -> Function {
+        fn_poly(self.coefficients.clone())
+    }
+
+    /// the polynomial f is, if it is a constant or the `Poly` variant
+    pub fn from_function(f: &Function) -> Option<Self> {
+        match f {
+            Function::Const(c) => Some(Polynomial::new(vec![*c])),
+            Function::Poly(coefficients) => Some(Polynomial::new(coefficients.clone())),
+            _ => None,
+        }
     }
 
+    #[allow(dead_code)]
     pub fn to_function_of_x(&self) -> Function {
         let mut terms = Vec::new();
         for (i, c) in self.coefficients.iter().enumerate() {
@@ -46,12 +136,100 @@ impl Polynomial {
     }
 }
 
-pub fn poly_eval(coeffs: &[f64], x: f64) -> f64 {
+impl EvaluateMany for Polynomial {
+    /// evaluates in x_0, the first column of `points`
+    fn eval_batch(&self, points: &ArrayView2<f64>) -> Array1<f64> {
+        match points.ncols() {
+            0 => Array1::from_elem(points.nrows(), poly_eval(&self.coefficients, 0.0)),
+            _ => points.column(0).mapv(|x| poly_eval(&self.coefficients, x)),
+        }
+    }
+}
+
+impl EvaluateOne for Polynomial {
+    fn eval_one(&self, x: f64) -> f64 {
+        self.eval(x)
+    }
+}
+
+// arithmetic ------------------------------------------------------------------
+
+impl Add for Polynomial {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Polynomial::new(poly_add(&self.coefficients, &other.coefficients))
+    }
+}
+
+impl Sub for Polynomial {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Polynomial::new(poly_sub(&self.coefficients, &other.coefficients))
+    }
+}
+
+impl Neg for Polynomial {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Polynomial::new(poly_scale(&self.coefficients, -1.0))
+    }
+}
+
+impl Mul for Polynomial {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Polynomial::new(poly_mul(&self.coefficients, &other.coefficients))
+    }
+}
+
+/// highest power first, e.g. `2x^3 - x + 0.5`
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let terms = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, &c)| c != 0.0);
+        for (i, (k, &c)) in terms.enumerate() {
+            match (i, c < 0.0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+
+            // the coefficient 1 is implied, except on the constant term
+            let c = c.abs();
+            if c != 1.0 || k == 0 {
+                write!(f, "{}", c)?;
+            }
+            match k {
+                0 => {}
+                1 => write!(f, "x")?,
+                _ => write!(f, "x^{}", k)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// coefficient vectors ---------------------------------------------------------
+
+/// evaluates the polynomial at x using horner's method
+pub fn poly_eval<S: Scalar>(coeffs: &[f64], x: S) -> S {
     coeffs
         .iter()
-        .enumerate()
-        .map(|(i, c)| c * x.powi(i as i32))
-        .sum()
+        .rev()
+        .fold(S::zero(), |acc, &c| acc * x.clone() + S::from_f64(c))
 }
 
 pub fn poly_scale(coeffs: &[f64], scalar: f64) -> Vec<f64> {
@@ -59,6 +237,10 @@ pub fn poly_scale(coeffs: &[f64], scalar: f64) -> Vec<f64> {
 }
 
 pub fn poly_mul(coeffs1: &[f64], coeffs2: &[f64]) -> Vec<f64> {
+    if coeffs1.is_empty() || coeffs2.is_empty() {
+        return Vec::new();
+    }
+
     let mut coeffs = vec![0.0; coeffs1.len() + coeffs2.len() - 1];
 
     for (k, c) in coeffs.iter_mut().enumerate() {
@@ -83,6 +265,344 @@ pub fn poly_derivative(coeffs: &[f64]) -> Vec<f64> {
     new_coeffs
 }
 
+pub fn poly_add(coeffs1: &[f64], coeffs2: &[f64]) -> Vec<f64> {
+    let n = coeffs1.len().max(coeffs2.len());
+    (0..n)
+        .map(|i| coeffs1.get(i).unwrap_or(&0.0) + coeffs2.get(i).unwrap_or(&0.0))
+        .collect()
+}
+
+pub fn poly_sub(coeffs1: &[f64], coeffs2: &[f64]) -> Vec<f64> {
+    poly_add(coeffs1, &poly_scale(coeffs2, -1.0))
+}
+
+/// drops the highest coefficients that are 0, the zero polynomial becomes []
+pub fn poly_trim(coeffs: &[f64]) -> Vec<f64> {
+    let len = coeffs.iter().rposition(|&c| c != 0.0).map_or(0, |i| i + 1);
+    coeffs[..len].to_vec()
+}
+
+/// polynomial long division: returns (q, r) with num = q*den + r and
+/// deg r < deg den
+///
+/// panics if den is the zero polynomial
+pub fn poly_div_rem(num: &[f64], den: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    let den = poly_trim(den);
+    let lead = *den.last().expect("division by the zero polynomial");
+
+    let mut rem = poly_trim(num);
+    if rem.len() < den.len() {
+        return (Vec::new(), rem);
+    }
+
+    let mut quot = vec![0.0; rem.len() - den.len() + 1];
+    for k in (0..quot.len()).rev() {
+        // cancel the highest remaining term with q_k*x^k*den
+        quot[k] = rem[k + den.len() - 1] / lead;
+        for (i, d) in den.iter().enumerate() {
+            rem[k + i] -= quot[k] * d;
+        }
+    }
+
+    rem.truncate(den.len() - 1);
+    (quot, poly_trim(&rem))
+}
+
+/// coefficients smaller than this, relative to the largest, count as 0 in
+/// `poly_gcd`
+const GCD_TOLERANCE: f64 = 1e-9;
+
+/// greatest common divisor by the euclidean algorithm, monic (or [] if both
+/// are the zero polynomial)
+///
+/// with floating point coefficients the remainders rarely vanish exactly, so
+/// coefficients below `GCD_TOLERANCE` (relative to the larger polynomial,
+/// then to each remainder) are dropped
+pub fn poly_gcd(coeffs1: &[f64], coeffs2: &[f64]) -> Vec<f64> {
+    let max_abs = |p: &[f64]| p.iter().fold(0.0, |m: f64, c| m.max(c.abs()));
+    let scale = max_abs(coeffs1).max(max_abs(coeffs2));
+
+    // scaled to a largest coefficient of 1, without the negligible ones
+    let normalize = |p: &[f64], scale: f64| {
+        if max_abs(p) <= GCD_TOLERANCE * scale {
+            return Vec::new();
+        }
+        let m = max_abs(p);
+        let p: Vec<f64> = p
+            .iter()
+            .map(|c| {
+                if c.abs() <= GCD_TOLERANCE * m {
+                    0.0
+                } else {
+                    c / m
+                }
+            })
+            .collect();
+        poly_trim(&p)
+    };
+
+    let mut a = normalize(coeffs1, scale);
+    let mut b = normalize(coeffs2, scale);
+    while !b.is_empty() {
+        let (_, r) = poly_div_rem(&a, &b);
+        a = std::mem::replace(&mut b, normalize(&r, 1.0));
+    }
+
+    match a.last() {
+        Some(&lead) => poly_scale(&a, 1.0 / lead),
+        None => a,
+    }
+}
+
+/// splits the polynomial into a_1 * a_2^2 * ... * a_k^k (times its leading
+/// coefficient) with monic, square-free and pairwise coprime a_i, by Yun's
+/// algorithm. returns the a_i of positive degree with their exponent i
+///
+/// the roots of a_i are exactly the roots of multiplicity i, and are simple
+/// roots of a_i, which root finders locate far more accurately
+pub fn poly_square_free_factors(coeffs: &[f64]) -> Vec<(Vec<f64>, usize)> {
+    let f = poly_trim(coeffs);
+    if f.len() < 2 {
+        return Vec::new();
+    }
+
+    let df = poly_derivative(&f);
+    let g = poly_gcd(&f, &df);
+    let mut b = poly_div_rem(&f, &g).0;
+    let mut d = poly_sub(&poly_div_rem(&df, &g).0, &poly_derivative(&b));
+
+    let mut factors = Vec::new();
+    let mut i = 1;
+    while b.len() > 1 {
+        let a = poly_gcd(&b, &d);
+        b = poly_div_rem(&b, &a).0;
+        d = poly_sub(&poly_div_rem(&d, &a).0, &poly_derivative(&b));
+        if a.len() > 1 {
+            factors.push((a, i));
+        }
+        i += 1;
+    }
+
+    factors
+}
+
+/// most iterations of `poly_complex_roots`
+const ROOT_ITERATIONS: usize = 500;
+
+/// most newton steps `poly_complex_roots` polishes each root with
+const POLISH_STEPS: usize = 3;
+
+/// all complex roots of the polynomial, repeated by multiplicity, with the
+/// Aberth-Ehrlich method and newton polishing
+///
+/// refines approximations of all roots at once, starting on a circle that
+/// encloses them: each takes a newton step corrected for the pull of the
+/// others. simple roots converge cubically to machine precision, a root of
+/// multiplicity m only to about eps^(1/m) (see `poly_square_free_factors`)
+pub fn poly_complex_roots(coeffs: &[f64]) -> Vec<Complex64> {
+    let coeffs = poly_trim(coeffs);
+    if coeffs.len() < 2 {
+        return Vec::new();
+    }
+
+    let n = coeffs.len() - 1;
+    let monic = poly_scale(&coeffs, 1.0 / coeffs[n]);
+    let derivative = poly_derivative(&monic);
+
+    // cauchy bound: every root has |z| <= 1 + max_k |c_k / c_n|
+    let radius = 1.0 + monic[..n].iter().fold(0.0, |m: f64, c| m.max(c.abs()));
+    // the angle offset keeps the start away from symmetric positions
+    let mut roots: Vec<Complex64> = (0..n)
+        .map(|k| Complex64::from_polar(radius, (2.0 * PI * k as f64 + 0.4) / n as f64))
+        .collect();
+
+    let mut converged = vec![false; n];
+    for _ in 0..ROOT_ITERATIONS {
+        for i in 0..n {
+            if converged[i] {
+                continue;
+            }
+
+            let z = roots[i];
+            let newton = poly_eval(&monic, z) / poly_eval(&derivative, z);
+            let repulsion: Complex64 = (0..n)
+                .filter(|&j| j != i)
+                .map(|j| (z - roots[j]).inv())
+                .sum();
+
+            let delta = newton / (1.0 - newton * repulsion);
+            if delta.is_finite() {
+                roots[i] = z - delta;
+                converged[i] = delta.norm() <= f64::EPSILON * (1.0 + z.norm());
+            }
+        }
+
+        if converged.iter().all(|&c| c) {
+            break;
+        }
+    }
+
+    let derivative = poly_derivative(&coeffs);
+    roots
+        .into_iter()
+        .map(|z| polish_root(&coeffs, &derivative, z))
+        .collect()
+}
+
+/// newton steps on the original coefficients from z, for as long as they
+/// make |p(z)| smaller
+fn polish_root(coeffs: &[f64], derivative: &[f64], mut z: Complex64) -> Complex64 {
+    let mut residual = poly_eval(coeffs, z).norm();
+    for _ in 0..POLISH_STEPS {
+        let step = z - poly_eval(coeffs, z) / poly_eval(derivative, z);
+        let step_residual = poly_eval(coeffs, step).norm();
+        if step_residual.is_nan() || step_residual >= residual {
+            break;
+        }
+        (z, residual) = (step, step_residual);
+    }
+    z
+}
+
+// roots -----------------------------------------------------------------------
+
+impl Polynomial {
+    /// the polynomial (x - r_1) * ... * (x - r_n)
+    /// all complex roots, repeated by multiplicity, sorted by real part and
+    /// then imaginary part (see `poly_complex_roots`)
+    pub fn roots(&self) -> Vec<Complex64> {
+        let mut roots = poly_complex_roots(&self.coefficients);
+        roots.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+        roots
+    }
+
+    /// the sturm sequence p, p', -rem(p, p'), ..., where each polynomial is the
+    /// negated remainder of the previous two, until it vanishes
+    ///
+    /// every entry is scaled to a largest coefficient of 1 (which keeps the
+    /// signs), and remainders below `GCD_TOLERANCE` count as 0
+    pub fn sturm_sequence(&self) -> Vec<Polynomial> {
+        let normalize = |p: Polynomial| {
+            let m = p.coefficients.iter().fold(0.0, |m: f64, c| m.max(c.abs()));
+            Polynomial::new(poly_scale(&p.coefficients, 1.0 / m))
+        };
+
+        let mut sequence = Vec::new();
+        if self.is_zero() {
+            return sequence;
+        }
+        sequence.push(normalize(self.clone().trimmed()));
+
+        let mut next = self.derivative();
+        while !next.is_zero() {
+            let next_normalized = normalize(next);
+            let (_, rem) = sequence[sequence.len() - 1].div_rem(&next_normalized);
+            sequence.push(next_normalized);
+
+            let size = rem
+                .coefficients
+                .iter()
+                .fold(0.0, |m: f64, c| m.max(c.abs()));
+            next = if size <= GCD_TOLERANCE {
+                Polynomial::zero()
+            } else {
+                -rem
+            };
+        }
+        sequence
+    }
+
+    /// disjoint intervals (a_i, b_i] covering the distinct real roots in (a, b],
+    /// each containing exactly one root, found by bisecting the interval while
+    /// sturm's theorem counts more than one
+    ///
+    /// roots closer together than tol, or than adjacent floats, share an
+    /// interval. panics unless tol > 0
+    pub fn isolate_real_roots(&self, interval: (f64, f64), tol: f64) -> Vec<(f64, f64)> {
+        assert!(tol > 0.0, "tolerance must be positive, got {tol}");
+        let sequence = self.sturm_sequence();
+        let count = |(a, b): (f64, f64)| count_real_roots(&sequence, (a, b));
+
+        let mut isolated = Vec::new();
+        let mut stack = vec![(interval, count(interval))];
+        while let Some(((a, b), n)) = stack.pop() {
+            match n {
+                0 => {}
+                1 => isolated.push((a, b)),
+                _ if b - a <= tol => isolated.push((a, b)),
+                _ => {
+                    let m = (a + b) / 2.0;
+                    if m <= a || m >= b {
+                        isolated.push((a, b));
+                        continue;
+                    }
+                    let left = count((a, m));
+                    // right half first, so intervals come out left to right
+                    stack.push(((m, b), n.saturating_sub(left)));
+                    stack.push(((a, m), left));
+                }
+            }
+        }
+        isolated
+    }
+
+    /// the distinct real roots in (a, b] to within tol, in increasing order, by
+    /// bisecting each isolating interval (see `isolate_real_roots`), or until
+    /// its ends are adjacent floats. panics unless tol > 0
+    ///
+    /// bisects on the sign of the square-free part p / gcd(p, p'), where every
+    /// root is simple, so roots of even multiplicity (where p doesn't change
+    /// sign) are found as well
+    pub fn real_roots(&self, interval: (f64, f64), tol: f64) -> Vec<f64> {
+        let g = poly_gcd(&self.coefficients, &poly_derivative(&self.coefficients));
+        let square_free = self.div_rem(&Polynomial::new(g)).0;
+
+        self.isolate_real_roots(interval, tol)
+            .into_iter()
+            .map(|(mut a, mut b)| {
+                // the interval is half-open, so the root may be b itself
+                if square_free.eval(b) == 0.0 {
+                    return b;
+                }
+                let positive_at_b = square_free.eval(b) > 0.0;
+                while b - a > tol {
+                    let m = (a + b) / 2.0;
+                    if m <= a || m >= b {
+                        break;
+                    }
+                    let y = square_free.eval(m);
+                    if y == 0.0 {
+                        return m;
+                    }
+                    if (y > 0.0) == positive_at_b {
+                        b = m;
+                    } else {
+                        a = m;
+                    }
+                }
+                (a + b) / 2.0
+            })
+            .collect()
+    }
+}
+
+/// number of distinct real roots in the half-open interval (a, b], by sturm's
+/// theorem on the sequence (see `Polynomial::sturm_sequence`)
+fn count_real_roots(sequence: &[Polynomial], (a, b): (f64, f64)) -> usize {
+    count_sign_changes(sequence, a).saturating_sub(count_sign_changes(sequence, b))
+}
+
+/// number of sign changes in the sturm sequence evaluated at x, zeros skipped
+fn count_sign_changes(sequence: &[Polynomial], x: f64) -> usize {
+    let signs: Vec<bool> = sequence
+        .iter()
+        .map(|p| p.eval(x))
+        .filter(|&y| y != 0.0)
+        .map(|y| y > 0.0)
+        .collect();
+    signs.windows(2).filter(|w| w[0] != w[1]).count()
+}
+
 // legendre polynomials ========================================================
 
 /// Returns the coefficients of the nth Legendre polynomial using the Rodrigues formula.
@@ -120,6 +640,148 @@ pub fn get_legendre_rodrigues(n: usize) -> Vec<f64> {
 mod tests {
     use super::*;
 
+    /// the monic polynomial with the given roots
+    fn with_roots(roots: &[f64]) -> Polynomial {
+        roots.iter().fold(Polynomial::new(vec![1.0]), |p, &r| {
+            p * Polynomial::new(vec![-r, 1.0])
+        })
+    }
+
+    #[test]
+    fn test_algebra() {
+        let p = Polynomial::new(vec![1.0, -2.0, 0.0, 3.0]);
+        let q = Polynomial::new(vec![-1.0, 1.0]);
+
+        assert_eq!(Polynomial::zero().degree(), 0);
+        assert_eq!(
+            Polynomial::new_with_coefficients(&[2.0, 1.0, 0.0, 0.0]).degree(),
+            1
+        );
+        assert_eq!(Polynomial::new(vec![0.0, 0.0]), Polynomial::zero());
+        assert!((p.clone() - p.clone()).is_zero());
+
+        // division with remainder
+        let (quot, rem) = p.div_rem(&q);
+        assert_eq!(quot.clone() * q.clone() + rem.clone(), p);
+        assert_eq!(rem.degree(), 0);
+        assert_eq!(rem.eval(0.0), p.eval(1.0));
+
+        // (p∘q)(x) = p(x - 1)
+        let composed = p.compose(&q);
+        assert_eq!(composed.degree(), 3);
+        for x in [-1.5f64, 0.0, 0.25, 2.0] {
+            assert!((composed.eval(x) - p.eval(x - 1.0)).abs() < 1e-12);
+            assert_eq!((-p.clone()).eval_one(x), -p.eval(x));
+        }
+
+        assert_eq!(p.derivative(), Polynomial::new(vec![-2.0, 0.0, 9.0]));
+        assert_eq!(p.antiderivative().derivative(), p);
+        assert_eq!(Polynomial::from_function(&p.to_function()), Some(p));
+    }
+
+    #[test]
+    fn test_roots() {
+        // (x - 2)(x + 0.5)^3 (x^2 + 1)
+        let p = with_roots(&[2.0, -0.5, -0.5, -0.5]) * Polynomial::new(vec![1.0, 0.0, 1.0]);
+        let roots = p.roots();
+        assert_eq!(roots.len(), 6);
+        for z in &roots {
+            assert!(p.eval(*z).norm() < 1e-12, "p({z}) = {}", p.eval(*z));
+        }
+        assert!((roots[5] - Complex64::new(2.0, 0.0)).norm() < 1e-12);
+        let i = Complex64::new(0.0, 1.0);
+        assert!(roots.iter().any(|z| (z - i).norm() < 1e-12));
+        assert!(roots.iter().any(|z| (z + i).norm() < 1e-12));
+
+        // wilkinson's polynomial, badly conditioned
+        let expected: Vec<f64> = (1..=10).map(|k| k as f64).collect();
+        let roots = with_roots(&expected).roots();
+        for (z, r) in roots.iter().zip(&expected) {
+            assert!((z - r).norm() < 1e-6, "{z} != {r}");
+        }
+
+        assert!(Polynomial::new(vec![3.0]).roots().is_empty());
+    }
+
+    #[test]
+    fn test_sturm() {
+        // x^3 - x = (x + 1) x (x - 1)
+        let p = Polynomial::new(vec![0.0, -1.0, 0.0, 1.0]);
+        let sequence = p.sturm_sequence();
+        assert_eq!(count_real_roots(&sequence, (-2.0, 2.0)), 3);
+        assert_eq!(count_real_roots(&sequence, (0.0, 2.0)), 1);
+        assert_eq!(count_real_roots(&sequence, (-1.0, 0.0)), 1);
+        assert_eq!(count_real_roots(&sequence, (5.0, 6.0)), 0);
+
+        let intervals = p.isolate_real_roots((-2.0, 2.0), 1e-9);
+        assert_eq!(intervals.len(), 3);
+        for (a, b) in intervals {
+            assert_eq!(count_real_roots(&sequence, (a, b)), 1);
+        }
+
+        // distinct roots only, including the double root at 1 where p doesn't change sign
+        let p = with_roots(&[1.0, 1.0, -3.0]) * Polynomial::new(vec![2.0, 0.0, 1.0]);
+        assert_eq!(count_real_roots(&p.sturm_sequence(), (-10.0, 10.0)), 2);
+        let roots = p.real_roots((-10.0, 10.0), 1e-10);
+        assert_eq!(roots.len(), 2);
+        assert!((roots[0] + 3.0).abs() < 1e-9);
+        assert!((roots[1] - 1.0).abs() < 1e-9);
+
+        // a tolerance below the float spacing stops at adjacent floats
+        let roots = with_roots(&[1e6 + 0.3]).real_roots((0.0, 2e6), 1e-300);
+        assert_eq!(roots.len(), 1);
+        assert!((roots[0] - (1e6 + 0.3)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_polynomial_division() {
+        let assert_close =
+            |a: f64, b: f64| assert!((a - b).abs() <= 1e-9 * (1.0 + b.abs()), "{a} != {b}");
+
+        // (x^3 - 1) = (x - 1)(x^2 + x + 1)
+        let (q, r) = poly_div_rem(&[-1.0, 0.0, 0.0, 1.0], &[-1.0, 1.0]);
+        assert_eq!(q, vec![1.0, 1.0, 1.0]);
+        assert!(r.is_empty());
+
+        let (q, r) = poly_div_rem(&[1.0, 2.0, 3.0], &[1.0, 0.0, 0.0, 1.0]);
+        assert!(q.is_empty());
+        assert_eq!(r, vec![1.0, 2.0, 3.0]);
+
+        // gcd((x - 1)(x - 2)^2, (x - 2)(x + 3)) = x - 2
+        let a = poly_mul(&poly_mul(&[-1.0, 1.0], &[-2.0, 1.0]), &[-2.0, 1.0]);
+        let b = poly_mul(&[-2.0, 1.0], &[3.0, 1.0]);
+        let g = poly_gcd(&a, &b);
+        assert_eq!(g.len(), 2);
+        assert_close(g[0], -2.0);
+
+        // a = (x - 1) * (x - 2)^2
+        let factors = poly_square_free_factors(&a);
+        assert_eq!(factors.len(), 2);
+        assert_eq!(factors[0].1, 1);
+        assert_close(factors[0].0[0], -1.0);
+        assert_eq!(factors[1].1, 2);
+        assert_close(factors[1].0[0], -2.0);
+    }
+
+    #[test]
+    fn test_display() {
+        let p = Polynomial::new(vec![0.5, -1.0, 0.0, 2.0]);
+        assert_eq!(p.to_string(), "2x^3 - x + 0.5");
+        assert_eq!((-p).to_string(), "-2x^3 + x - 0.5");
+        assert_eq!(Polynomial::new(vec![-1.0, 0.0, 1.0]).to_string(), "x^2 - 1");
+        assert_eq!(Polynomial::zero().to_string(), "0");
+    }
+
+    #[test]
+    fn test_eval_batch() {
+        let p = Polynomial::new_with_coefficients(&[1.0, -2.0, 3.0]);
+        let points = ndarray::arr2(&[[0.0, 5.0], [1.0, 5.0], [-2.0, 5.0]]);
+        assert_eq!(
+            p.eval_batch(&points.view()),
+            ndarray::arr1(&[1.0, 2.0, 17.0])
+        );
+    }
+
     #[test]
     fn test_legendre() {
         for i in 0..=10 {
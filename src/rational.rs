@@ -0,0 +1,492 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// -----------------------------------------------------------------------------
+
+use crate::func::{EvaluateOne, Function};
+use crate::polynomial::{
+    poly_add, poly_complex_roots, poly_div_rem, poly_eval, poly_gcd, poly_mul, poly_scale,
+    poly_square_free_factors, poly_trim,
+};
+
+// =============================================================================
+
+/// the rational function num(x) / den(x), both polynomials in x_0 given by
+/// their coefficients (lowest degree first)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rational {
+    pub num: Vec<f64>,
+    pub den: Vec<f64>,
+}
+
+impl Rational {
+    /// panics if den is the zero polynomial
+    pub fn new(num: &[f64], den: &[f64]) -> Self {
+        assert!(
+            !poly_trim(den).is_empty(),
+            "denominator is the zero polynomial"
+        );
+        Rational {
+            num: poly_trim(num),
+            den: poly_trim(den),
+        }
+    }
+
+    pub fn from_polynomial(coeffs: &[f64]) -> Self {
+        Rational::new(coeffs, &[1.0])
+    }
+
+    /// the rational function f is, if it is built from constants, x_0,
+    /// polynomials and arithmetic (including integer powers)
+    pub fn from_function(f: &Function) -> Option<Self> {
+        let r = match f {
+            Function::Const(c) => Rational::from_polynomial(&[*c]),
+            Function::Var(0) => Rational::from_polynomial(&[0.0, 1.0]),
+            Function::Poly(coeffs) => Rational::from_polynomial(coeffs),
+            Function::Add(f1, f2) => Rational::from_function(f1)? + Rational::from_function(f2)?,
+            Function::Sub(f1, f2) => Rational::from_function(f1)? - Rational::from_function(f2)?,
+            Function::Mul(f1, f2) => Rational::from_function(f1)? * Rational::from_function(f2)?,
+            Function::Div(f1, f2) => {
+                let den = Rational::from_function(f2)?;
+                if den.num.is_empty() {
+                    return None;
+                }
+                Rational::from_function(f1)? / den
+            }
+            Function::Neg(g) => -Rational::from_function(g)?,
+            Function::Sum(fs) => fs
+                .iter()
+                .map(Rational::from_function)
+                .try_fold(Rational::from_polynomial(&[]), |acc, r| Some(acc + r?))?,
+            Function::Prod(fs) => fs
+                .iter()
+                .map(Rational::from_function)
+                .try_fold(Rational::from_polynomial(&[1.0]), |acc, r| Some(acc * r?))?,
+            Function::PowI(g, n) => Rational::from_function(g)?.powi(*n)?,
+            _ => return None,
+        };
+        Some(r)
+    }
+
+    /// the equal rational function in lowest terms: common factors cancelled
+    /// (see `poly_gcd`) and a monic denominator
+    pub fn reduce(&self) -> Self {
+        if self.num.is_empty() {
+            return Rational::from_polynomial(&[]);
+        }
+
+        let g = poly_gcd(&self.num, &self.den);
+        let num = poly_div_rem(&self.num, &g).0;
+        let den = poly_div_rem(&self.den, &g).0;
+
+        let lead = den[den.len() - 1];
+        Rational::new(&poly_scale(&num, 1.0 / lead), &poly_scale(&den, 1.0 / lead))
+    }
+
+    /// the n-th power, none if n is negative and this is 0
+    pub fn powi(&self, n: i32) -> Option<Self> {
+        let base = if n >= 0 {
+            self.clone()
+        } else if self.num.is_empty() {
+            return None;
+        } else {
+            Rational::new(&self.den, &self.num)
+        };
+        Some(
+            (0..n.unsigned_abs()).fold(Rational::from_polynomial(&[1.0]), |acc, _| {
+                acc * base.clone()
+            }),
+        )
+    }
+
+    pub fn eval(&self, x: f64) -> f64 {
+        poly_eval(&self.num, x) / poly_eval(&self.den, x)
+    }
+
+    /// the real poles, sorted, with their order: the real roots of the
+    /// reduced denominator with their multiplicity
+    pub fn poles(&self) -> Vec<(f64, usize)> {
+        let mut poles: Vec<(f64, usize)> = real_factors(&self.reduce().den)
+            .into_iter()
+            .filter_map(|(factor, m)| match factor {
+                Factor::Linear(root) => Some((root, m)),
+                Factor::Quadratic(_, _) => None,
+            })
+            .collect();
+        poles.sort_by(|a, b| a.0.total_cmp(&b.0));
+        poles
+    }
+
+    /// decomposes into a polynomial plus a sum of terms a/(x - r)^k and
+    /// (bx + c)/(x^2 + px + q)^k, with one term for every power k up to the
+    /// multiplicity of each real factor of the denominator
+    ///
+    /// the factors come from the roots of the square-free factors of the
+    /// denominator, the numerators from solving the linear system that
+    /// matching coefficients gives. none if that system is singular, which
+    /// happens when roots too close to tell apart count as distinct factors
+    pub fn partial_fractions(&self) -> Option<PartialFractions> {
+        let r = self.reduce();
+        let (polynomial, rem) = poly_div_rem(&r.num, &r.den);
+        let n = r.den.len() - 1;
+
+        let factors = real_factors(&r.den);
+
+        // the denominator is prod_j f_j^m_j (monic after reducing), so the term
+        // over f_j^k has the numerator times prod_{i != j} f_i^m_i * f_j^(m_j - k)
+        let mut columns = Vec::new();
+        let mut unknowns = Vec::new();
+        for (j, (factor, m)) in factors.iter().enumerate() {
+            let others = factors
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| i != j)
+                .fold(vec![1.0], |acc, (_, (f, m))| {
+                    poly_mul(&acc, &poly_pow(&f.coeffs(), *m))
+                });
+
+            for k in 1..=*m {
+                let basis = poly_mul(&others, &poly_pow(&factor.coeffs(), m - k));
+                if let Factor::Quadratic(_, _) = factor {
+                    columns.push(poly_mul(&basis, &[0.0, 1.0]));
+                }
+                columns.push(basis);
+                unknowns.push((*factor, k));
+            }
+        }
+
+        let mut matrix = vec![vec![0.0; n]; n];
+        for (col, basis) in columns.iter().enumerate() {
+            for (row, c) in basis.iter().enumerate().take(n) {
+                matrix[row][col] = *c;
+            }
+        }
+        let mut rhs = rem;
+        rhs.resize(n, 0.0);
+        let solution = solve_linear(matrix, rhs)?;
+
+        let mut values = solution.into_iter();
+        let terms = unknowns
+            .into_iter()
+            .map(|(factor, power)| match factor {
+                Factor::Linear(root) => PartialFraction::Linear {
+                    a: values.next().unwrap(),
+                    root,
+                    power,
+                },
+                Factor::Quadratic(p, q) => PartialFraction::Quadratic {
+                    b: values.next().unwrap(),
+                    c: values.next().unwrap(),
+                    p,
+                    q,
+                    power,
+                },
+            })
+            .collect();
+
+        Some(PartialFractions { polynomial, terms })
+    }
+}
+
+impl EvaluateOne for Rational {
+    fn eval_one(&self, x: f64) -> f64 {
+        self.eval(x)
+    }
+
+    fn singularities(&self, (a, b): (f64, f64)) -> Vec<f64> {
+        self.poles()
+            .into_iter()
+            .map(|(root, _)| root)
+            .filter(|root| (a..=b).contains(root))
+            .collect()
+    }
+}
+
+// arithmetic ------------------------------------------------------------------
+
+// results are reduced, so repeated operations don't grow the degree
+
+impl Add for Rational {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let num = poly_add(
+            &poly_mul(&self.num, &other.den),
+            &poly_mul(&other.num, &self.den),
+        );
+        Rational::new(&num, &poly_mul(&self.den, &other.den)).reduce()
+    }
+}
+
+impl Sub for Rational {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + (-other)
+    }
+}
+
+impl Neg for Rational {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Rational::new(&poly_scale(&self.num, -1.0), &self.den)
+    }
+}
+
+impl Mul for Rational {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Rational::new(
+            &poly_mul(&self.num, &other.num),
+            &poly_mul(&self.den, &other.den),
+        )
+        .reduce()
+    }
+}
+
+impl Div for Rational {
+    type Output = Self;
+
+    /// panics if other is 0
+    fn div(self, other: Self) -> Self {
+        Rational::new(
+            &poly_mul(&self.num, &other.den),
+            &poly_mul(&self.den, &other.num),
+        )
+        .reduce()
+    }
+}
+
+// partial fractions -----------------------------------------------------------
+
+/// polynomial + sum of terms, see `Rational::partial_fractions`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialFractions {
+    pub polynomial: Vec<f64>,
+    pub terms: Vec<PartialFraction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PartialFraction {
+    /// a / (x - root)^power
+    Linear { a: f64, root: f64, power: usize },
+    /// (b*x + c) / (x^2 + p*x + q)^power, where x^2 + p*x + q has no real roots
+    Quadratic {
+        b: f64,
+        c: f64,
+        p: f64,
+        q: f64,
+        power: usize,
+    },
+}
+
+// factoring -------------------------------------------------------------------
+
+/// roots with an imaginary part below this (relative to their size) are real
+const IMAGINARY_TOLERANCE: f64 = 1e-8;
+
+/// monic irreducible real factor of a polynomial
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Factor {
+    /// x - root
+    Linear(f64),
+    /// x^2 + p*x + q with p^2 < 4q
+    Quadratic(f64, f64),
+}
+
+impl Factor {
+    fn coeffs(&self) -> Vec<f64> {
+        match *self {
+            Factor::Linear(root) => vec![-root, 1.0],
+            Factor::Quadratic(p, q) => vec![q, p, 1.0],
+        }
+    }
+}
+
+/// the irreducible real factors of the polynomial with their multiplicity,
+/// from the roots of its square-free factors: a real root r gives x - r, a
+/// pair of complex conjugate roots z, z* gives x^2 - 2 Re(z) x + |z|^2
+fn real_factors(coeffs: &[f64]) -> Vec<(Factor, usize)> {
+    let mut factors = Vec::new();
+    for (a, m) in poly_square_free_factors(coeffs) {
+        for z in poly_complex_roots(&a) {
+            if z.im.abs() <= IMAGINARY_TOLERANCE * (1.0 + z.norm()) {
+                factors.push((Factor::Linear(z.re), m));
+            } else if z.im > 0.0 {
+                factors.push((Factor::Quadratic(-2.0 * z.re, z.norm_sqr()), m));
+            }
+        }
+    }
+    factors
+}
+
+fn poly_pow(coeffs: &[f64], n: usize) -> Vec<f64> {
+    (0..n).fold(vec![1.0], |acc, _| poly_mul(&acc, coeffs))
+}
+
+/// pivots up to this, relative to the largest entry, make `solve_linear`
+/// give up on the system as singular
+const PIVOT_TOLERANCE: f64 = 1e-12;
+
+/// solves the square system a*x = b by gaussian elimination with partial
+/// pivoting, a given as rows. none if it is singular up to rounding
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    let scale = a.iter().flatten().fold(0.0, |m: f64, x| m.max(x.abs()));
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap();
+        if a[pivot][col].abs() <= PIVOT_TOLERANCE * scale {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_row[col];
+            for (x, p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::func::{fn_add, fn_const, fn_div, fn_powi, fn_sin, X};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() <= 1e-9 * (1.0 + b.abs()), "{a} != {b}");
+    }
+
+    /// evaluates the decomposition term by term
+    fn eval_partial_fractions(pf: &PartialFractions, x: f64) -> f64 {
+        let terms = pf.terms.iter().map(|term| match *term {
+            PartialFraction::Linear { a, root, power } => a / (x - root).powi(power as i32),
+            PartialFraction::Quadratic { b, c, p, q, power } => {
+                (b * x + c) / (x * x + p * x + q).powi(power as i32)
+            }
+        });
+        poly_eval(&pf.polynomial, x) + terms.sum::<f64>()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // 1/(x - 1) - 1/(x + 1) = 2/(x^2 - 1)
+        let r1 = Rational::new(&[1.0], &[-1.0, 1.0]);
+        let r2 = Rational::new(&[1.0], &[1.0, 1.0]);
+        let diff = r1.clone() - r2.clone();
+        assert_eq!(diff.num.len(), 1);
+        assert_close(diff.num[0], 2.0);
+        assert_eq!(diff.den.len(), 3);
+
+        // reducing cancels (x - 1)
+        let r = Rational::new(&[-2.0, 2.0], &poly_mul(&[-1.0, 1.0], &[3.0, 1.0])).reduce();
+        assert_eq!(r.den.len(), 2);
+        assert_close(r.num[0], 2.0);
+        assert_close(r.den[0], 3.0);
+
+        for x in [-0.5, 0.3, 2.5] {
+            assert_close((r1.clone() * r2.clone()).eval(x), r1.eval(x) * r2.eval(x));
+            assert_close(r1.powi(-2).unwrap().eval(x), (x - 1.0) * (x - 1.0));
+        }
+        assert_eq!(Rational::from_polynomial(&[]).powi(-1), None);
+
+        // (x^2 + 1) / (x + 1)^-2
+        let f = fn_div(
+            fn_add(fn_powi(X, 2), fn_const(1.0)),
+            fn_powi(fn_add(X, fn_const(1.0)), -2),
+        );
+        let r = Rational::from_function(&f).unwrap();
+        assert_eq!(r.den, vec![1.0]);
+        assert_close(r.eval(2.0), 45.0);
+        assert_eq!(Rational::from_function(&fn_sin(X)), None);
+        let zero = Function::PowI(Box::new(fn_const(0.0)), -1);
+        assert_eq!(Rational::from_function(&zero), None);
+    }
+
+    #[test]
+    fn test_partial_fractions() {
+        // 1/(x^2 - 1) = (1/2)/(x - 1) - (1/2)/(x + 1)
+        let pf = Rational::new(&[1.0], &[-1.0, 0.0, 1.0])
+            .partial_fractions()
+            .unwrap();
+        assert!(pf.polynomial.is_empty());
+        assert_eq!(pf.terms.len(), 2);
+        for term in &pf.terms {
+            match *term {
+                PartialFraction::Linear { a, root, power } => {
+                    assert_eq!(power, 1);
+                    assert_close(a, root / 2.0);
+                }
+                _ => panic!("{term:?}"),
+            }
+        }
+
+        // (x^5 + 2) / ((x - 1)^2 (x + 2) (x^2 + x + 1)^2)
+        let den = [
+            poly_pow(&[-1.0, 1.0], 2),
+            vec![2.0, 1.0],
+            poly_pow(&[1.0, 1.0, 1.0], 2),
+        ]
+        .iter()
+        .fold(vec![3.0], |acc, f| poly_mul(&acc, f));
+        let r = Rational::new(&[2.0, 0.0, 0.0, 0.0, 0.0, 1.0], &den);
+        let pf = r.partial_fractions().unwrap();
+
+        let count = |linear: bool, power: usize| {
+            pf.terms
+                .iter()
+                .filter(|t| match t {
+                    PartialFraction::Linear { power: k, .. } => linear && *k == power,
+                    PartialFraction::Quadratic { power: k, .. } => !linear && *k == power,
+                })
+                .count()
+        };
+        assert_eq!((count(true, 1), count(true, 2)), (2, 1));
+        assert_eq!((count(false, 1), count(false, 2)), (1, 1));
+
+        for x in [-3.0, -1.5, 0.0, 0.5, 2.0, 10.0] {
+            assert_close(eval_partial_fractions(&pf, x), r.eval(x));
+        }
+
+        // improper: (x^3 + 1) / (x^2 + 1) = x + (1 - x) / (x^2 + 1)
+        let pf = Rational::new(&[1.0, 0.0, 0.0, 1.0], &[1.0, 0.0, 1.0])
+            .partial_fractions()
+            .unwrap();
+        assert_eq!(pf.polynomial.len(), 2);
+        assert_close(pf.polynomial[1], 1.0);
+        assert_close(
+            eval_partial_fractions(&pf, 0.7),
+            (0.7f64.powi(3) + 1.0) / (0.49 + 1.0),
+        );
+
+        assert_eq!(r.poles().len(), 2);
+        assert_close(r.poles()[0].0, -2.0);
+        assert_eq!(r.poles()[1].1, 2);
+    }
+
+    #[test]
+    fn test_solve_linear() {
+        let a = vec![vec![0.0, 2.0], vec![3.0, 1.0]];
+        assert_eq!(solve_linear(a, vec![4.0, 5.0]), Some(vec![1.0, 2.0]));
+
+        // the rows only differ by rounding
+        let a = vec![vec![1.0, 2.0], vec![1.0, 2.0 + 1e-15]];
+        assert_eq!(solve_linear(a, vec![1.0, 1.0]), None);
+    }
+}
@@ -0,0 +1,495 @@
+use std::fmt;
+
+// -----------------------------------------------------------------------------
+
+use crate::func::{fn_pdv, Function};
+use crate::util::sample_interval_equidistributed;
+use crate::EvaluateOne;
+
+// =============================================================================
+
+/// stopping criteria shared by the root finding methods
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerances {
+    /// stop once the root is known to within this distance (for bracketing
+    /// methods), or the last step was at most this long (for the others)
+    pub x_tol: f64,
+    /// stop once |f(x)| is at most this, 0 to only stop at an exact zero
+    pub f_tol: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Tolerances {
+            x_tol: 1e-12,
+            f_tol: 0.0,
+            max_iterations: 100,
+        }
+    }
+}
+
+/// why a method stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// the root is known to within `x_tol`
+    XTolerance,
+    /// |f(root)| is at most `f_tol`
+    FTolerance,
+    /// gave up after `max_iterations`
+    MaxIterations,
+    /// f doesn't change sign over the bracket it was given
+    NotBracketed,
+    /// the derivative or secant slope vanished, or an iterate isn't finite
+    Breakdown,
+}
+
+/// diagnostics of a run of a root finding method
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Convergence {
+    /// the last estimate of the root
+    pub root: f64,
+    /// f(root)
+    pub value: f64,
+    pub iterations: usize,
+    pub termination: Termination,
+}
+
+impl Convergence {
+    /// true if the method stopped because a tolerance was met
+    pub fn converged(&self) -> bool {
+        matches!(
+            self.termination,
+            Termination::XTolerance | Termination::FTolerance
+        )
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::XTolerance => write!(f, "x tolerance met"),
+            Termination::FTolerance => write!(f, "f tolerance met"),
+            Termination::MaxIterations => write!(f, "out of iterations"),
+            Termination::NotBracketed => write!(f, "not bracketed"),
+            Termination::Breakdown => write!(f, "broke down"),
+        }
+    }
+}
+
+impl fmt::Display for Convergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "x = {:.10}, f(x) = {:.1e} after {} steps, {}",
+            self.root, self.value, self.iterations, self.termination
+        )
+    }
+}
+
+fn report(root: f64, value: f64, iterations: usize, termination: Termination) -> Convergence {
+    Convergence {
+        root,
+        value,
+        iterations,
+        termination,
+    }
+}
+
+// bracketing methods ----------------------------------------------------------
+
+/// finds a root of f in [a, b], where f(a) and f(b) have opposite signs, by
+/// halving the bracket. slow (one bit per iteration) but can't fail
+pub fn bisection<F: EvaluateOne + ?Sized>(
+    f: &F,
+    (mut a, mut b): (f64, f64),
+    tol: &Tolerances,
+) -> Convergence {
+    let (fa, fb) = (f.eval_one(a), f.eval_one(b));
+    if fa == 0.0 {
+        return report(a, fa, 0, Termination::FTolerance);
+    }
+    if fb == 0.0 {
+        return report(b, fb, 0, Termination::FTolerance);
+    }
+    if !is_bracket(fa, fb) {
+        return report(b, fb, 0, Termination::NotBracketed);
+    }
+
+    let a_positive = fa > 0.0;
+    for iteration in 1..=tol.max_iterations {
+        let m = (a + b) / 2.0;
+        let fm = f.eval_one(m);
+
+        if fm.abs() <= tol.f_tol {
+            return report(m, fm, iteration, Termination::FTolerance);
+        }
+        if (b - a) / 2.0 <= tol.x_tol {
+            return report(m, fm, iteration, Termination::XTolerance);
+        }
+
+        if (fm > 0.0) == a_positive {
+            a = m;
+        } else {
+            b = m;
+        }
+    }
+
+    let m = (a + b) / 2.0;
+    report(
+        m,
+        f.eval_one(m),
+        tol.max_iterations,
+        Termination::MaxIterations,
+    )
+}
+
+/// finds a root of f in [a, b], where f(a) and f(b) have opposite signs, with
+/// Brent's method
+///
+/// keeps a bracket like bisection, but steps by inverse quadratic
+/// interpolation or the secant rule wherever that makes faster progress, so
+/// it converges superlinearly on smooth f and never slower than bisection
+pub fn brent<F: EvaluateOne + ?Sized>(f: &F, (a, b): (f64, f64), tol: &Tolerances) -> Convergence {
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f.eval_one(a), f.eval_one(b));
+    if fa == 0.0 {
+        return report(a, fa, 0, Termination::FTolerance);
+    }
+    if fb == 0.0 {
+        return report(b, fb, 0, Termination::FTolerance);
+    }
+    if !is_bracket(fa, fb) {
+        return report(b, fb, 0, Termination::NotBracketed);
+    }
+
+    // b is the best estimate, c the other end of the bracket, a the previous b
+    let (mut c, mut fc) = (b, fb);
+    let mut d = b - a;
+    let mut e = d;
+
+    for iteration in 1..=tol.max_iterations {
+        if (fb > 0.0) == (fc > 0.0) {
+            (c, fc) = (a, fa);
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, fa) = (b, fb);
+            (b, fb) = (c, fc);
+            (c, fc) = (a, fa);
+        }
+
+        let tol1 = 2.0 * f64::EPSILON * b.abs() + 0.5 * tol.x_tol;
+        let m = 0.5 * (c - b);
+        if fb.abs() <= tol.f_tol {
+            return report(b, fb, iteration, Termination::FTolerance);
+        }
+        if m.abs() <= tol1 {
+            return report(b, fb, iteration, Termination::XTolerance);
+        }
+
+        if e.abs() >= tol1 && fa.abs() > fb.abs() {
+            // interpolate: secant through a and b, or inverse quadratic
+            // through a, b and c
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * m * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (
+                    s * (2.0 * m * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            } else {
+                p = -p;
+            }
+
+            // only accept the step if it stays inside the bracket and shrinks
+            // faster than bisection would
+            if 2.0 * p < (3.0 * m * q - (tol1 * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = m;
+                e = d;
+            }
+        } else {
+            d = m;
+            e = d;
+        }
+
+        (a, fa) = (b, fb);
+        b += if d.abs() > tol1 { d } else { tol1.copysign(m) };
+        fb = f.eval_one(b);
+    }
+
+    report(b, fb, tol.max_iterations, Termination::MaxIterations)
+}
+
+fn is_bracket(fa: f64, fb: f64) -> bool {
+    fa.is_finite() && fb.is_finite() && (fa > 0.0) != (fb > 0.0)
+}
+
+// open methods ----------------------------------------------------------------
+
+/// finds a root of f near x0 and x1 with the secant method: newton's method
+/// with the derivative replaced by the slope through the last two iterates
+pub fn secant<F: EvaluateOne + ?Sized>(
+    f: &F,
+    (x0, x1): (f64, f64),
+    tol: &Tolerances,
+) -> Convergence {
+    let (mut x0, mut x1) = (x0, x1);
+    let (mut f0, mut f1) = (f.eval_one(x0), f.eval_one(x1));
+
+    for iteration in 1..=tol.max_iterations {
+        if f1.abs() <= tol.f_tol {
+            return report(x1, f1, iteration - 1, Termination::FTolerance);
+        }
+
+        let x2 = x1 - f1 * (x1 - x0) / (f1 - f0);
+        if !x2.is_finite() {
+            return report(x1, f1, iteration, Termination::Breakdown);
+        }
+
+        (x0, f0) = (x1, f1);
+        (x1, f1) = (x2, f.eval_one(x2));
+
+        if (x1 - x0).abs() <= tol.x_tol {
+            return report(x1, f1, iteration, Termination::XTolerance);
+        }
+    }
+
+    report(x1, f1, tol.max_iterations, Termination::MaxIterations)
+}
+
+/// finds a root of f (in x_0) near x0 with newton's method, using the
+/// symbolic derivative from `fn_pdv`
+///
+/// converges quadratically close to a simple root, but may wander off or
+/// break down (where f' = 0) from a poor start
+pub fn newton(f: &Function, x0: f64, tol: &Tolerances) -> Convergence {
+    let df = fn_pdv(f, 0).compile();
+    newton_with_derivative(&f.compile(), &df, x0, tol)
+}
+
+/// newton's method with a given derivative df of f
+pub fn newton_with_derivative<F, D>(f: &F, df: &D, x0: f64, tol: &Tolerances) -> Convergence
+where
+    F: EvaluateOne + ?Sized,
+    D: EvaluateOne + ?Sized,
+{
+    let mut x = x0;
+    let mut fx = f.eval_one(x);
+
+    for iteration in 1..=tol.max_iterations {
+        if fx.abs() <= tol.f_tol {
+            return report(x, fx, iteration - 1, Termination::FTolerance);
+        }
+
+        let step = fx / df.eval_one(x);
+        if !step.is_finite() {
+            return report(x, fx, iteration, Termination::Breakdown);
+        }
+
+        x -= step;
+        fx = f.eval_one(x);
+
+        if step.abs() <= tol.x_tol {
+            return report(x, fx, iteration, Termination::XTolerance);
+        }
+    }
+
+    report(x, fx, tol.max_iterations, Termination::MaxIterations)
+}
+
+// scanning --------------------------------------------------------------------
+
+/// settings of `find_roots_with`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanOptions {
+    /// number of equal pieces the interval is scanned in for sign changes,
+    /// two roots within one piece can be missed
+    pub samples: usize,
+    pub tolerances: Tolerances,
+    /// an extremum where |f| is at most this counts as a root of even
+    /// multiplicity, which has no sign change to bracket
+    pub touch_tol: f64,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            samples: 1000,
+            tolerances: Tolerances::default(),
+            touch_tol: 1e-10,
+        }
+    }
+}
+
+/// finds the roots of f (in x_0) in the interval, sorted, with the
+/// diagnostics of the run that found each. df is the derivative of f,
+/// supplied so callers scanning the same function repeatedly can compile both
+/// once
+///
+/// samples f on a grid of `options.samples` pieces. exact zeros are roots
+/// right away, pieces where f changes sign are handed to `brent`, and local
+/// minima of |f| without a sign change to `brent` on f' in search of roots of
+/// even multiplicity. sign changes across a pole (known singularities, or
+/// where |f| grows instead of vanishing) are skipped
+///
+/// runs that stopped without meeting a tolerance are included, check
+/// `Convergence::converged`
+pub fn find_roots_with<F, D>(
+    compiled: &F,
+    df: &D,
+    interval: (f64, f64),
+    options: &ScanOptions,
+) -> Vec<Convergence>
+where
+    F: EvaluateOne + ?Sized,
+    D: EvaluateOne + ?Sized,
+{
+    let tol = &options.tolerances;
+
+    let poles = compiled.singularities(interval);
+    let xs = sample_interval_equidistributed(interval, options.samples);
+    let ys = compiled.eval_each(&xs);
+
+    let mut roots = Vec::new();
+    for i in 0..xs.len() {
+        let (a, fa) = (xs[i], ys[i]);
+
+        if fa == 0.0 {
+            roots.push(report(a, fa, 0, Termination::FTolerance));
+            continue;
+        }
+        if i + 1 == xs.len() {
+            continue;
+        }
+        let (b, fb) = (xs[i + 1], ys[i + 1]);
+
+        if fb != 0.0 && is_bracket(fa, fb) {
+            if poles.iter().any(|&p| a <= p && p <= b) {
+                continue;
+            }
+
+            let run = brent(compiled, (a, b), tol);
+            // converging onto a pole, |f| grows instead of vanishing
+            if run.value.abs() <= fa.abs().max(fb.abs()) {
+                roots.push(run);
+            }
+        } else if i > 0 && is_touching(ys[i - 1], fa, fb) {
+            let run = brent(df, (xs[i - 1], b), tol);
+            let value = compiled.eval_one(run.root);
+            if run.converged() && value.abs() <= options.touch_tol {
+                roots.push(Convergence { value, ..run });
+            }
+        }
+    }
+
+    roots.sort_by(|r, s| r.root.total_cmp(&s.root));
+    roots
+}
+
+/// true if |f| has a local minimum at the middle of three samples of the
+/// same sign
+fn is_touching(f0: f64, f1: f64, f2: f64) -> bool {
+    let finite = f0.is_finite() && f1.is_finite() && f2.is_finite();
+    let same_sign = (f0 > 0.0) == (f1 > 0.0) && (f1 > 0.0) == (f2 > 0.0);
+    finite && same_sign && f1.abs() < f0.abs() && f1.abs() <= f2.abs()
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::func::*;
+
+    #[test]
+    fn test_methods() {
+        // cos(x) = x
+        let f = fn_sub(fn_cos(X), X);
+        let root = 0.739_085_133_215_160_6;
+        let tol = Tolerances::default();
+
+        let runs = [
+            bisection(&f, (0.0, 1.0), &tol),
+            brent(&f, (0.0, 1.0), &tol),
+            secant(&f, (0.0, 1.0), &tol),
+            newton(&f, 0.0, &tol),
+        ];
+        for run in &runs {
+            assert!(run.converged(), "{run:?}");
+            assert!((run.root - root).abs() < 1e-11, "{run:?}");
+        }
+
+        // brent and newton need far fewer steps than bisection's ~40
+        assert!(runs[1].iterations < 15 && runs[3].iterations < 10);
+        assert!(runs[0].iterations > 30);
+
+        // stopping on the residual
+        let loose = Tolerances { f_tol: 1e-3, ..tol };
+        let run = bisection(&f, (0.0, 1.0), &loose);
+        assert_eq!(run.termination, Termination::FTolerance);
+        assert!(run.value.abs() <= 1e-3);
+        assert!(run.to_string().ends_with("f tolerance met"), "{run}");
+
+        // failures
+        let g = fn_poly(vec![-1.0, 0.0, 1.0]);
+        assert_eq!(
+            brent(&g, (-0.5, 0.5), &tol).termination,
+            Termination::NotBracketed
+        );
+        assert_eq!(newton(&g, 0.0, &tol).termination, Termination::Breakdown);
+        let few = Tolerances {
+            max_iterations: 3,
+            ..tol
+        };
+        assert_eq!(
+            bisection(&f, (0.0, 1.0), &few).termination,
+            Termination::MaxIterations
+        );
+    }
+
+    #[test]
+    fn test_find_roots() {
+        let options = ScanOptions::default();
+        let roots = |f: &Function, interval| -> Vec<f64> {
+            let df = fn_pdv(f, 0).compile();
+            find_roots_with(&f.compile(), &df, interval, &options)
+                .iter()
+                .filter(|r| r.converged())
+                .map(|r| r.root)
+                .collect()
+        };
+
+        // -3pi, ..., 3pi
+        let found = roots(&fn_sin(X), (-10.0, 10.0));
+        assert_eq!(found.len(), 7);
+        for (k, x) in found.iter().enumerate() {
+            let expected = (k as f64 - 3.0) * std::f64::consts::PI;
+            assert!((x - expected).abs() < 1e-10, "{x} != {expected}");
+        }
+
+        // the sign changes at the poles +-pi/2 are not roots
+        let found = roots(&fn_tan(X), (-2.0, 2.0));
+        assert_eq!(found.len(), 1);
+        assert!(found[0].abs() < 1e-12);
+        let found = roots(&fn_div(fn_const(1.0), fn_poly(vec![-0.5, 1.0])), (0.0, 1.0));
+        assert!(found.is_empty());
+
+        // double root at 1, simple root at -2
+        let f = fn_poly_with_roots(&[1.0, 1.0, -2.0]);
+        let found = roots(&f, (-3.0, 3.0));
+        assert_eq!(found.len(), 2);
+        assert!((found[0] + 2.0).abs() < 1e-10);
+        assert!((found[1] - 1.0).abs() < 1e-6);
+    }
+}
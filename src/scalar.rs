@@ -0,0 +1,83 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use num_complex::{Complex32, Complex64};
+
+// =============================================================================
+
+/// number types a `Function` can be evaluated over
+///
+/// covers exactly the operations the `Function` variants need, so anything
+/// from plain floats to complex numbers can share one expression tree
+pub trait Scalar:
+    Clone
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// embeds a real constant
+    fn from_f64(c: f64) -> Self;
+
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn exp(self) -> Self;
+    /// natural logarithm
+    fn ln(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+
+    fn zero() -> Self {
+        Self::from_f64(0.0)
+    }
+
+    fn one() -> Self {
+        Self::from_f64(1.0)
+    }
+}
+
+// implementations -------------------------------------------------------------
+
+/// implements `Scalar` by forwarding to the type's inherent methods (which
+/// take precedence over the trait's in method call syntax), `powi` is passed
+/// in since complex numbers take it by reference
+macro_rules! impl_scalar {
+    ($t:ty, $from:expr, $powi:expr) => {
+        impl Scalar for $t {
+            fn from_f64(c: f64) -> Self {
+                $from(c)
+            }
+
+            fn sin(self) -> Self {
+                self.sin()
+            }
+
+            fn cos(self) -> Self {
+                self.cos()
+            }
+
+            fn tan(self) -> Self {
+                self.tan()
+            }
+
+            fn exp(self) -> Self {
+                self.exp()
+            }
+
+            fn ln(self) -> Self {
+                self.ln()
+            }
+
+            fn powi(self, n: i32) -> Self {
+                $powi(self, n)
+            }
+        }
+    };
+}
+
+impl_scalar!(f64, |c| c, f64::powi);
+impl_scalar!(f32, |c| c as f32, f32::powi);
+impl_scalar!(Complex64, Complex64::from, |z, n| Complex64::powi(&z, n));
+impl_scalar!(Complex32, |c| Complex32::from(c as f32), |z, n| {
+    Complex32::powi(&z, n)
+});
@@ -0,0 +1,605 @@
+use std::collections::HashSet;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// -----------------------------------------------------------------------------
+
+use crate::func::Function;
+
+// =============================================================================
+
+/// a rewrite rule: returns the rewritten expression if the rule applies to
+/// the root of f (whose subexpressions are already simplified) and changes it
+pub struct Rule {
+    pub name: &'static str,
+    pub apply: fn(&Function) -> Option<Function>,
+}
+
+/// the rules `Function::simplify` uses, tried in order
+///
+/// the result is a canonical form built from n-ary `Sum`s and `Prod`s:
+/// `Add`, `Sub`, `Neg`, `Mul` and `Div` are rewritten in terms of them
+/// (a - b = a + (-1)*b, a / b = a * b^-1), constants come first in each node
+/// and the remaining operands are sorted by the `Ord` of `Function`.
+/// polynomials are expanded into sums of powers of their variable
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "binary to n-ary",
+        apply: binary_to_nary,
+    },
+    Rule {
+        name: "polynomials to sums",
+        apply: expand_polynomial,
+    },
+    Rule {
+        name: "fold constants",
+        apply: fold_constants,
+    },
+    Rule {
+        name: "powers",
+        apply: power_rules,
+    },
+    Rule {
+        name: "trig, exp and log identities",
+        apply: function_identities,
+    },
+    Rule {
+        name: "normalize sum",
+        apply: normalize_sum,
+    },
+    Rule {
+        name: "normalize product",
+        apply: normalize_prod,
+    },
+];
+
+/// simplifies f bottom up: the subexpressions of every node are rewritten to
+/// a fixpoint first, then the rules are applied to the node itself until none
+/// applies
+///
+/// rules must only fire when they change the expression, or this won't halt
+pub fn rewrite(f: &Function, rules: &[Rule]) -> Function {
+    Rewriter {
+        rules,
+        normal: HashSet::new(),
+    }
+    .rewrite(f)
+}
+
+/// remembers the expressions already at a fixpoint, so the parts of a node a
+/// rule carries over into its result aren't rewritten again
+struct Rewriter<'a> {
+    rules: &'a [Rule],
+    normal: HashSet<Function>,
+}
+
+impl Rewriter<'_> {
+    fn rewrite(&mut self, f: &Function) -> Function {
+        if self.normal.contains(f) {
+            return f.clone();
+        }
+
+        let mut f = f.map_children(|g| self.rewrite(g));
+        // a rule may build new nodes between the node and its simplified
+        // children, those are rewritten before the next rule is tried
+        let apply = |f: &Function| {
+            self.rules
+                .iter()
+                .find_map(|rule| Some((rule.name, (rule.apply)(f)?)))
+        };
+        while let Some((name, g)) = apply(&f) {
+            debug_assert!(g != f, "rule \"{name}\" fired without changing {f}");
+            f = g.map_children(|h| self.rewrite(h));
+        }
+
+        self.normal.insert(f.clone());
+        f
+    }
+}
+
+impl Function {
+    /// returns an equivalent, simplified expression in canonical form, so
+    /// that structurally equal results mean equal functions
+    ///
+    /// flattens sums and products, collects like terms and powers, folds
+    /// constants and applies identities like sin^2 + cos^2 = 1. identities
+    /// are only used where they hold for every real input, except that
+    /// cancelling (e.g. x/x = 1) may remove points where f was undefined
+    pub fn simplify(&self) -> Function {
+        rewrite(self, RULES)
+    }
+
+    /// true if f and g (very likely) define the same function
+    ///
+    /// equal canonical forms (see `simplify`) prove it. otherwise both are
+    /// compared at `EQUIVALENCE_PROBES` pseudo-random points in [-2, 2]^n,
+    /// where they must agree to a relative tolerance of 1e-9 or both be
+    /// undefined. the points are the same on every call, so the answer only
+    /// depends on f and g. a false positive from probing is unlikely but
+    /// possible
+    pub fn is_equivalent(&self, other: &Function) -> bool {
+        if self == other || self.simplify() == other.simplify() {
+            return true;
+        }
+
+        let n = self.num_vars().max(other.num_vars());
+        let mut rng = StdRng::seed_from_u64(EQUIVALENCE_SEED);
+        (0..EQUIVALENCE_PROBES).all(|_| {
+            let args: Vec<f64> = (0..n).map(|_| rng.gen_range(-2.0..2.0)).collect();
+            let (a, b): (f64, f64) = (self.eval(&args), other.eval(&args));
+            if a.is_finite() && b.is_finite() {
+                (a - b).abs() <= 1e-9 * (1.0 + a.abs().max(b.abs()))
+            } else {
+                a == b || (a.is_nan() && b.is_nan())
+            }
+        })
+    }
+}
+
+/// number of points `is_equivalent` evaluates both functions at
+const EQUIVALENCE_PROBES: usize = 32;
+
+/// seed of the generator for the points `is_equivalent` evaluates at
+const EQUIVALENCE_SEED: u64 = 0x0067_7261_6666;
+
+// rules -----------------------------------------------------------------------
+
+fn binary_to_nary(f: &Function) -> Option<Function> {
+    let g = match f {
+        Function::Add(a, b) => Function::Sum(vec![*a.clone(), *b.clone()]),
+        Function::Sub(a, b) => Function::Sum(vec![*a.clone(), negate(b)]),
+        Function::Neg(a) => negate(a),
+        Function::Mul(a, b) => Function::Prod(vec![*a.clone(), *b.clone()]),
+        Function::Div(a, b) => Function::Prod(vec![*a.clone(), Function::PowI(b.clone(), -1)]),
+        _ => return None,
+    };
+    Some(g)
+}
+
+fn fold_constants(f: &Function) -> Option<Function> {
+    let c = match f {
+        Function::Sin(g) => g.as_const()?.sin(),
+        Function::Cos(g) => g.as_const()?.cos(),
+        Function::Tan(g) => g.as_const()?.tan(),
+        Function::Exp(g) => g.as_const()?.exp(),
+        Function::Log(g) => g.as_const()?.ln(),
+        Function::PowI(g, n) => g.as_const()?.powi(*n),
+        _ => return None,
+    };
+    Some(constant(c))
+}
+
+/// sum_k c_k * x_i^k, where x_i is x_0 for `Poly`
+fn expand_polynomial(f: &Function) -> Option<Function> {
+    let term = |c: Function, i: usize, k: usize| {
+        Function::Prod(vec![
+            c,
+            Function::PowI(Box::new(Function::Var(i)), k as i32),
+        ])
+    };
+    let terms = match f {
+        Function::Poly(coeffs) => coeffs
+            .iter()
+            .enumerate()
+            .map(|(k, &c)| term(constant(c), 0, k))
+            .collect(),
+        Function::PolyF(fs, i) => fs
+            .iter()
+            .enumerate()
+            .map(|(k, g)| term(g.clone(), *i, k))
+            .collect(),
+        _ => return None,
+    };
+    Some(Function::Sum(terms))
+}
+
+fn power_rules(f: &Function) -> Option<Function> {
+    let Function::PowI(g, n) = f else {
+        return None;
+    };
+    let g = match (&**g, *n) {
+        (_, 0) => constant(1.0),
+        (g, 1) => g.clone(),
+        // (g^m)^n = g^(mn) for integer powers
+        (Function::PowI(h, m), n) => Function::PowI(h.clone(), m.checked_mul(n)?),
+        (Function::Prod(fs), n) => Function::Prod(
+            fs.iter()
+                .map(|h| Function::PowI(Box::new(h.clone()), n))
+                .collect(),
+        ),
+        (Function::Exp(u), n) => Function::Exp(Box::new(scale(n as f64, *u.clone()))),
+        _ => return None,
+    };
+    Some(g)
+}
+
+fn function_identities(f: &Function) -> Option<Function> {
+    let g = match f {
+        Function::Log(g) => match &**g {
+            Function::Exp(u) => *u.clone(),
+            _ => return None,
+        },
+        // sin and tan are odd, cos is even
+        Function::Sin(u) if is_negative_term(u) => negate(&Function::Sin(Box::new(negate(u)))),
+        Function::Tan(u) if is_negative_term(u) => negate(&Function::Tan(Box::new(negate(u)))),
+        Function::Cos(u) if is_negative_term(u) => Function::Cos(Box::new(negate(u))),
+        _ => return None,
+    };
+    Some(g)
+}
+
+/// flattens nested sums, adds up constants and collects like terms
+/// (2x + 3x = 5x), then sorts the terms
+fn normalize_sum(f: &Function) -> Option<Function> {
+    let Function::Sum(terms) = f else {
+        return None;
+    };
+
+    let mut constant_term = 0.0;
+    let mut collected: Vec<(f64, Function)> = Vec::new();
+    for term in flatten(terms, |g| match g {
+        Function::Sum(gs) => Some(gs),
+        _ => None,
+    }) {
+        match split_coefficient(term) {
+            (c, None) => constant_term += c,
+            (c, Some(rest)) => match collected.iter_mut().find(|(_, r)| *r == rest) {
+                Some((total, _)) => *total += c,
+                None => collected.push((c, rest)),
+            },
+        }
+    }
+
+    pythagorean_identity(&mut constant_term, &mut collected);
+
+    collected.retain(|(c, _)| *c != 0.0);
+    collected.sort_by(|(_, a), (_, b)| a.cmp(b));
+
+    let mut result = Vec::new();
+    if constant_term != 0.0 {
+        result.push(constant(constant_term));
+    }
+    result.extend(collected.into_iter().map(|(c, g)| scale(c, g)));
+
+    let g = match result.len() {
+        0 => constant(0.0),
+        1 => result.pop().unwrap(),
+        _ => Function::Sum(result),
+    };
+    (g != *f).then_some(g)
+}
+
+/// a*sin(u)^2 + b*cos(u)^2 = b + (a - b)*sin(u)^2
+fn pythagorean_identity(constant_term: &mut f64, terms: &mut [(f64, Function)]) {
+    for i in 0..terms.len() {
+        let Some(u) = squared_argument(&terms[i].1, true) else {
+            continue;
+        };
+        let cos_squared = terms
+            .iter()
+            .position(|(c, g)| *c != 0.0 && squared_argument(g, false).is_some_and(|v| u == v));
+        if let Some(j) = cos_squared {
+            let b = terms[j].0;
+            *constant_term += b;
+            terms[i].0 -= b;
+            terms[j].0 = 0.0;
+        }
+    }
+}
+
+/// u if g is sin(u)^2 (or cos(u)^2 if `sin` is false)
+fn squared_argument(g: &Function, sin: bool) -> Option<&Function> {
+    match (g, sin) {
+        (Function::PowI(h, 2), true) => match &**h {
+            Function::Sin(u) => Some(u),
+            _ => None,
+        },
+        (Function::PowI(h, 2), false) => match &**h {
+            Function::Cos(u) => Some(u),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// flattens nested products, multiplies out constants, collects powers of
+/// the same base (x * x^2 = x^3) and exponentials (e^a * e^b = e^(a + b)),
+/// turns sin(u)^n * cos(u)^-n into tan(u)^n, then sorts the factors
+fn normalize_prod(f: &Function) -> Option<Function> {
+    let Function::Prod(factors) = f else {
+        return None;
+    };
+
+    let mut coefficient = 1.0;
+    let mut exponents = Vec::new();
+    let mut powers: Vec<(Function, i32)> = Vec::new();
+    for factor in flatten(factors, |g| match g {
+        Function::Prod(gs) => Some(gs),
+        _ => None,
+    }) {
+        let (base, n) = match factor {
+            Function::Const(c) => {
+                coefficient *= c;
+                continue;
+            }
+            Function::Exp(u) => {
+                exponents.push(*u);
+                continue;
+            }
+            Function::PowI(base, n) => (*base, n),
+            base => (base, 1),
+        };
+        match powers.iter_mut().find(|(b, _)| *b == base) {
+            Some((_, total)) => *total += n,
+            None => powers.push((base, n)),
+        }
+    }
+
+    if coefficient == 0.0 {
+        let zero = constant(0.0);
+        return (zero != *f).then_some(zero);
+    }
+
+    match exponents.len() {
+        0 => {}
+        1 => powers.push((Function::Exp(Box::new(exponents.pop().unwrap())), 1)),
+        _ => powers.push((Function::Exp(Box::new(Function::Sum(exponents))), 1)),
+    }
+
+    // sin(u)^n * cos(u)^-n = tan(u)^n
+    for i in 0..powers.len() {
+        let (Function::Sin(u), n) = &powers[i] else {
+            continue;
+        };
+        let n = *n;
+        let cos = powers
+            .iter()
+            .position(|(g, m)| *m == -n && matches!(g, Function::Cos(v) if u == v));
+        if let Some(j) = cos {
+            powers[i].0 = Function::Tan(u.clone());
+            powers[j].1 = 0;
+        }
+    }
+
+    powers.retain(|(_, n)| *n != 0);
+
+    let mut result: Vec<Function> = powers
+        .into_iter()
+        .map(|(base, n)| match n {
+            1 => base,
+            n => Function::PowI(Box::new(base), n),
+        })
+        .collect();
+    result.sort();
+    if coefficient != 1.0 {
+        result.insert(0, constant(coefficient));
+    }
+
+    let g = match result.len() {
+        0 => constant(1.0),
+        1 => result.pop().unwrap(),
+        _ => Function::Prod(result),
+    };
+    (g != *f).then_some(g)
+}
+
+// helpers ---------------------------------------------------------------------
+
+impl Function {
+    fn as_const(&self) -> Option<f64> {
+        match self {
+            Function::Const(c) => Some(*c),
+            _ => None,
+        }
+    }
+}
+
+/// constant with -0 normalized to 0, so it compares equal to other zeros
+fn constant(c: f64) -> Function {
+    Function::Const(c + 0.0)
+}
+
+/// (-1)*f, with the sign absorbed into a leading constant factor
+fn negate(f: &Function) -> Function {
+    let (c, rest) = split_coefficient(f.clone());
+    match rest {
+        None => constant(-c),
+        Some(rest) => scale(-c, rest),
+    }
+}
+
+/// c*f for an f without constant factor
+fn scale(c: f64, f: Function) -> Function {
+    match f {
+        _ if c == 1.0 => f,
+        Function::Prod(mut fs) => {
+            fs.insert(0, constant(c));
+            Function::Prod(fs)
+        }
+        f => Function::Prod(vec![constant(c), f]),
+    }
+}
+
+/// splits a term into its constant coefficient and the rest (None if the
+/// term is constant), e.g. 3*x*y into (3, x*y)
+fn split_coefficient(f: Function) -> (f64, Option<Function>) {
+    match f {
+        Function::Const(c) => (c, None),
+        Function::Prod(mut fs) => match fs.first() {
+            Some(Function::Const(c)) => {
+                let c = *c;
+                fs.remove(0);
+                let rest = match fs.len() {
+                    0 => None,
+                    1 => fs.pop(),
+                    _ => Some(Function::Prod(fs)),
+                };
+                (c, rest)
+            }
+            _ => (1.0, Some(Function::Prod(fs))),
+        },
+        f => (1.0, Some(f)),
+    }
+}
+
+/// true if f has a negative coefficient, so -f is simpler than f
+fn is_negative_term(f: &Function) -> bool {
+    match f {
+        Function::Prod(fs) => matches!(fs.first(), Some(Function::Const(c)) if *c < 0.0),
+        _ => false,
+    }
+}
+
+/// collects the operands of an n-ary node, splicing in the operands of
+/// directly nested nodes of the same kind
+fn flatten<F>(fs: &[Function], nested: F) -> Vec<Function>
+where
+    F: Fn(&Function) -> Option<&Vec<Function>> + Copy,
+{
+    let mut result = Vec::new();
+    for f in fs {
+        match nested(f) {
+            Some(gs) => result.extend(flatten(gs, nested)),
+            None => result.push(f.clone()),
+        }
+    }
+    result
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::func::*;
+
+    /// checks that simplifying f gives g, is idempotent and doesn't change
+    /// the values of f
+    fn check(f: Function, g: Function) {
+        let s = f.simplify();
+        assert_eq!(s, g.simplify(), "{f}");
+        assert_eq!(s.simplify(), s);
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let args: Vec<f64> = (0..3).map(|_| rng.gen_range(0.1..2.0)).collect();
+            let (a, b): (f64, f64) = (f.eval(&args), s.eval(&args));
+            assert!((a - b).abs() <= 1e-9 * (1.0 + a.abs()), "{f} -> {s}");
+        }
+    }
+
+    #[test]
+    fn test_like_terms_and_powers() {
+        // x + x = 2x
+        check(fn_add(X, X), fn_mul(fn_const(2.0), X));
+        // x - x = 0
+        check(fn_sub(X, X), fn_const(0.0));
+        // x * x^2 / x = x^2
+        check(fn_div(fn_mul(X, fn_powi(X, 2)), X), fn_powi(X, 2));
+        // 2xy + 3yx - y*x*5 = 0
+        check(
+            fn_sum(vec![
+                fn_prod(vec![fn_const(2.0), X, Y]),
+                fn_prod(vec![fn_const(3.0), Y, X]),
+                fn_neg(fn_prod(vec![Y, X, fn_const(5.0)])),
+            ]),
+            fn_const(0.0),
+        );
+        // (x*y)^2 / y = x^2 y
+        check(
+            fn_div(fn_powi(fn_mul(X, Y), 2), Y),
+            fn_mul(fn_powi(X, 2), Y),
+        );
+        // constants fold inside n-ary nodes
+        check(
+            fn_sum(vec![fn_const(1.0), X, fn_const(2.0)]),
+            fn_add(X, fn_const(3.0)),
+        );
+    }
+
+    #[test]
+    fn test_polynomials() {
+        // the raw variant, fn_poly would already unwrap this one
+        check(Function::Poly(vec![0.0, 1.0]), X);
+        check(
+            Function::Poly(vec![1.0, 2.0]),
+            fn_add(fn_mul(fn_const(2.0), X), fn_const(1.0)),
+        );
+        // y + 3y x^2
+        check(
+            Function::PolyF(vec![Y, fn_const(0.0), fn_mul(fn_const(3.0), Y)], 0),
+            fn_add(Y, fn_prod(vec![fn_const(3.0), fn_powi(X, 2), Y])),
+        );
+    }
+
+    #[test]
+    fn test_identities() {
+        // sin^2 + cos^2 = 1
+        check(
+            fn_add(fn_powi(fn_sin(X), 2), fn_powi(fn_cos(X), 2)),
+            fn_const(1.0),
+        );
+        // 3 sin^2 + 2 cos^2 = 2 + sin^2
+        check(
+            fn_add(
+                fn_mul(fn_const(3.0), fn_powi(fn_sin(Y), 2)),
+                fn_mul(fn_const(2.0), fn_powi(fn_cos(Y), 2)),
+            ),
+            fn_add(fn_const(2.0), fn_powi(fn_sin(Y), 2)),
+        );
+        // e^x e^y = e^(x + y)
+        check(fn_mul(fn_exp(X), fn_exp(Y)), fn_exp(fn_add(Y, X)));
+        // log(e^x) = x
+        check(fn_log(fn_exp(X)), X);
+        // sin / cos = tan
+        check(fn_div(fn_sin(X), fn_cos(X)), fn_tan(X));
+        // parity
+        check(fn_sin(fn_neg(X)), fn_neg(fn_sin(X)));
+        check(fn_cos(fn_neg(X)), fn_cos(X));
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        // structurally different but equal expressions simplify to the same
+        check(
+            fn_add(fn_mul(Y, X), fn_sin(Z)),
+            fn_add(fn_sin(Z), fn_mul(X, Y)),
+        );
+        check(fn_sub(fn_add(X, Y), Z), fn_add(fn_neg(Z), fn_add(Y, X)));
+    }
+
+    #[test]
+    fn test_simplify_derivatives() {
+        // d/dx x^2 sin(x) = 2x sin(x) + x^2 cos(x)
+        let f = fn_mul(fn_powi(X, 2), fn_sin(X));
+        let df = fn_add(
+            fn_prod(vec![fn_const(2.0), X, fn_sin(X)]),
+            fn_mul(fn_powi(X, 2), fn_cos(X)),
+        );
+        check(fn_pdv(&f, 0), df);
+
+        // the 0 and 1 factors fn_pdv leaves in n-ary products are folded away
+        let g = fn_prod(vec![X, fn_exp(X), fn_const(4.0)]);
+        let dg = fn_pdv(&g, 0);
+        check(
+            dg,
+            fn_add(
+                fn_mul(fn_const(4.0), fn_exp(X)),
+                fn_prod(vec![fn_const(4.0), X, fn_exp(X)]),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_is_equivalent() {
+        // (x + 1)^2 isn't expanded by simplify, so this needs probing
+        assert!(fn_powi(fn_add(X, fn_const(1.0)), 2).is_equivalent(&fn_poly(vec![1.0, 2.0, 1.0])));
+        assert!(fn_div(fn_sin(Y), fn_cos(Y)).is_equivalent(&fn_tan(Y)));
+        assert!(fn_sub(fn_mul(X, Y), fn_mul(Y, X)).is_equivalent(&fn_const(0.0)));
+
+        assert!(!fn_sin(X).is_equivalent(&fn_cos(X)));
+        assert!(!fn_mul(X, Y).is_equivalent(&fn_mul(X, Z)));
+        // agree for x > 0 only
+        assert!(!fn_log(fn_powi(X, 2)).is_equivalent(&fn_mul(fn_const(2.0), fn_log(X))));
+    }
+}
@@ -16,6 +16,20 @@ impl Point {
     pub fn origin() -> Self {
         Point { x: 0.0, y: 0.0 }
     }
+
+    /// the point at distance r from the origin in direction theta (radians),
+    /// a negative r lands on the opposite side
+    pub fn from_polar(r: f64, theta: f64) -> Self {
+        Point {
+            x: r * theta.cos(),
+            y: r * theta.sin(),
+        }
+    }
+
+    /// returns (r, theta) with r >= 0 and theta in [-pi, pi]
+    pub fn to_polar(self) -> (f64, f64) {
+        (self.x.hypot(self.y), self.y.atan2(self.x))
+    }
 }
 
 impl std::ops::Add for Point {
@@ -50,4 +64,26 @@ pub fn sample_interval_random(interval: (f64, f64), steps: usize) -> Vec<f64> {
 
 pub fn factorial(n: u32) -> u64 {
     (1..=n as u64).product()
-}
\ No newline at end of file
+}
+
+// tests =======================================================================
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn test_polar() {
+        let p = Point::from_polar(2.0, PI / 3.0);
+        assert!((p.x - 1.0).abs() < 1e-12 && (p.y - 3f64.sqrt()).abs() < 1e-12);
+
+        let (r, theta) = Point::new(-1.0, -1.0).to_polar();
+        assert!((r - 2f64.sqrt()).abs() < 1e-12 && (theta + 3.0 * PI / 4.0).abs() < 1e-12);
+
+        // negative radius points the other way
+        let (r, theta) = Point::from_polar(-1.0, PI / 2.0).to_polar();
+        assert!((r - 1.0).abs() < 1e-12 && (theta + PI / 2.0).abs() < 1e-12);
+    }
+}